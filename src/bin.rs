@@ -1,53 +1,58 @@
-use std::{env, fs, thread};
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process, thread,
+};
 
-use pl_interpreter::{setup_default_environment, Interpreter};
+use pl_interpreter::{Interpreter, Vm};
+
+const USAGE: &str = "usage: pl_interpreter_bin [--engine tree|vm] [file]";
 
 fn main() {
-    let args = env::args().collect::<Vec<String>>();
-    let file_name = if args.len() > 1 {
-        args.get(1).unwrap().to_owned()
-    } else {
-        "test/main.amr".to_string()
+    let mut args = env::args().skip(1).collect::<Vec<String>>();
+    // the tree walking interpreter runs scripts by default, `--engine vm` uses the bytecode vm
+    let use_vm = match args.iter().position(|arg| arg == "--engine") {
+        Some(index) => {
+            let engine = args.get(index + 1).cloned().unwrap_or_default();
+            args.drain(index..(index + 2).min(args.len()));
+            match engine.as_str() {
+                "vm" => true,
+                "tree" => false,
+                engine => {
+                    eprintln!("Unknown engine \"{}\", expected tree or vm", engine);
+                    eprintln!("{}", USAGE);
+                    process::exit(2);
+                }
+            }
+        }
+        None => false,
+    };
+    let file_name = match args.first() {
+        Some(file_name) => file_name.to_owned(),
+        None => "test/main.amr".to_string(),
     };
 
     thread::Builder::new()
         .stack_size(1024 * 1024)
-        .spawn(|| {
-            let source = fs::read_to_string(file_name).unwrap();
-            // let mut parser = Parser::from_source(source);
-            // let ast = parser.produce_ast().unwrap();
-            // let mut interpreter = Box::new(Interpreter::new());
-
-            let env_id = setup_default_environment();
-
-            match Interpreter::run_from_source(source, env_id) {
-                Ok(res) => res,
-                Err(err) => panic!("{}", err),
+        .spawn(move || {
+            // non-relative imports are looked up in AMR_PATH, e.g. AMR_PATH=lib:vendor
+            let search_paths = env::var_os("AMR_PATH")
+                .map(|paths| env::split_paths(&paths).collect::<Vec<PathBuf>>())
+                .unwrap_or_default();
+            let result = if use_vm {
+                let mut vm = Vm::new().with_search_paths(search_paths);
+                vm.run_file(Path::new(&file_name))
+            } else {
+                let mut interpreter = Interpreter::new().with_search_paths(search_paths);
+                interpreter.run_file(Path::new(&file_name))
             };
 
-            // println!(
-            //     "\n\n-----------------------\n{:#?}",
-            //     SCOPE_STATE.lock().unwrap()
-            // );
-
-            // println!("result: {:?}", result);
+            if let Err(err) = result {
+                eprintln!("{}", err);
+                process::exit(1);
+            }
         })
         .unwrap()
         .join()
         .unwrap();
 }
-
-// fn loop_(cb: fn(usize), index: usize, limit: usize) {
-//     if index < limit {
-//         cb(index);
-//         loop_(cb, index + 1, limit);
-//     }
-// }
-
-// fn func(index: usize) {
-//     println!("{index}");
-// }
-
-// fn main() {
-//     loop_(func, 0, 1000000);
-// }
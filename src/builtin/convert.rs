@@ -1,37 +1,25 @@
-use std::sync::{Arc, Mutex};
+use crate::{InterpreterError, NativeContext, Value};
 
-use crate::{cast_value, ArrayValue, NullValue, ObjectValue, RuntimeValue, StringValue, ValueType};
+use super::display;
 
-use super::{mk_runtime_value, stringify};
-
-pub fn convert_to_string(arg: &Box<dyn RuntimeValue>) -> String {
-    match arg.kind() {
-        ValueType::Array => {
-            let arr = cast_value::<ArrayValue>(&arg).unwrap();
-            format!("<array ({} items)>", arr.value().len())
-        }
-        ValueType::Object => {
-            let obj = cast_value::<ObjectValue>(&arg).unwrap();
-            format!("<object ({} pairs)>", obj.map().len())
-        }
-        _ => stringify(dyn_clone::clone_box(&**arg)),
+pub fn convert_to_string(
+    context: &mut dyn NativeContext,
+    arg: &Value,
+) -> Result<String, InterpreterError> {
+    match arg {
+        Value::Array(arr) => Ok(format!("<array ({} items)>", arr.len())),
+        Value::Object(obj) => Ok(format!("<object ({} pairs)>", obj.map().len())),
+        _ => display(context, arg),
     }
 }
 
 pub fn native_string_convert(
-    args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>,
-) -> Arc<Mutex<Box<dyn RuntimeValue>>> {
-    if args.is_empty() {
-        return mk_runtime_value(Box::new(NullValue::default()));
-    }
-
-    let arg = args
-        .get(0)
-        .unwrap()
-        .lock()
-        .expect("string_converter: failed to get first argument");
-
-    let value = convert_to_string(&arg);
-
-    mk_runtime_value(Box::new(StringValue::from(value)))
+    context: &mut dyn NativeContext,
+    args: Vec<Value>,
+) -> Result<Value, InterpreterError> {
+    let result = match args.first() {
+        Some(arg) => Value::from(convert_to_string(context, arg)?),
+        None => Value::Null,
+    };
+    Ok(result)
 }
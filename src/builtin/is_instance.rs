@@ -0,0 +1,16 @@
+use crate::{InterpreterError, NativeContext, Value};
+
+/// is_instance(value, Class), also true for instances of subclasses
+pub fn native_is_instance(
+    _context: &mut dyn NativeContext,
+    args: Vec<Value>,
+) -> Result<Value, InterpreterError> {
+    let result = match args.as_slice() {
+        [Value::ClassInstance(instance), Value::Class(class)] => {
+            Value::from(instance.class().is_subclass_of(class))
+        }
+        [_, _] => Value::from(false),
+        _ => Value::Null,
+    };
+    Ok(result)
+}
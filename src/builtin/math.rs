@@ -1,247 +1,128 @@
 use core::f64;
+use pl_ast::BinaryOperator;
 use rand::Rng;
-use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
-};
+use std::{collections::HashMap, sync::Arc};
 
-use crate::{
-    cast_value, DecimalValue, IntegerValue, Key, NullValue, RuntimeValue, Value, ValueType,
-};
+use crate::{macros::bail, InterpreterError, Key, NativeContext, Value};
 
-use super::{mk_native_fn, mk_runtime_value};
+use super::mk_native_fn;
 
-fn random(_args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>) -> Arc<Mutex<Box<dyn RuntimeValue>>> {
+fn random(_context: &mut dyn NativeContext, _args: Vec<Value>) -> Result<Value, InterpreterError> {
     let mut rng = rand::thread_rng();
 
     let num = rng.gen::<f64>();
 
-    mk_runtime_value(Box::new(DecimalValue::from(num)))
+    Ok(Value::from(num))
 }
 
-fn floor(args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>) -> Arc<Mutex<Box<dyn RuntimeValue>>> {
-    if args.is_empty() {
-        return mk_runtime_value(Box::new(NullValue::default()));
+/// Applies the rounding to decimals, integers are already round
+fn round_with(args: &[Value], round: fn(f64) -> f64) -> Value {
+    match args.first() {
+        Some(Value::Decimal(decimal)) => Value::from(round(*decimal)),
+        Some(Value::Integer(integer)) => Value::from(*integer),
+        _ => Value::Null,
     }
-
-    let arg = args.get(0).unwrap();
-    let value = arg
-        .lock()
-        .expect("math.floor: failed to get first argument");
-
-    if value.kind() != ValueType::Decimal && value.kind() != ValueType::Integer {
-        return mk_runtime_value(Box::new(NullValue::default()));
-    }
-
-    if value.kind() == ValueType::Decimal {
-        let decimal = cast_value::<DecimalValue>(&value).unwrap();
-        return mk_runtime_value(Box::new(DecimalValue::from(decimal.value().floor())));
-    }
-
-    // only integer case left
-    return arg.clone();
 }
 
-fn ceil(args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>) -> Arc<Mutex<Box<dyn RuntimeValue>>> {
-    if args.is_empty() {
-        return mk_runtime_value(Box::new(NullValue::default()));
-    }
-
-    let arg = args.get(0).unwrap();
-    let value = arg.lock().expect("math.ceil: failed to get first argument");
-
-    if value.kind() != ValueType::Decimal && value.kind() != ValueType::Integer {
-        return mk_runtime_value(Box::new(NullValue::default()));
-    }
-
-    if value.kind() == ValueType::Decimal {
-        let decimal = cast_value::<DecimalValue>(&value).unwrap();
-        return mk_runtime_value(Box::new(DecimalValue::from(decimal.value().ceil())));
-    }
-
-    // only integer case left
-    return arg.clone();
+fn floor(_context: &mut dyn NativeContext, args: Vec<Value>) -> Result<Value, InterpreterError> {
+    Ok(round_with(&args, f64::floor))
 }
 
-fn trunc(args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>) -> Arc<Mutex<Box<dyn RuntimeValue>>> {
-    if args.is_empty() {
-        return mk_runtime_value(Box::new(NullValue::default()));
-    }
-
-    let arg = args.get(0).unwrap();
-    let value = arg
-        .lock()
-        .expect("math.trunc: failed to get first argument");
-
-    if value.kind() != ValueType::Decimal && value.kind() != ValueType::Integer {
-        return mk_runtime_value(Box::new(NullValue::default()));
-    }
-
-    if value.kind() == ValueType::Decimal {
-        let decimal = cast_value::<DecimalValue>(&value).unwrap();
-        return mk_runtime_value(Box::new(DecimalValue::from(decimal.value().trunc())));
-    }
-
-    // only integer case left
-    return arg.clone();
+fn ceil(_context: &mut dyn NativeContext, args: Vec<Value>) -> Result<Value, InterpreterError> {
+    Ok(round_with(&args, f64::ceil))
 }
 
-fn round(args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>) -> Arc<Mutex<Box<dyn RuntimeValue>>> {
-    if args.is_empty() {
-        return mk_runtime_value(Box::new(NullValue::default()));
-    }
-
-    let arg = args.get(0).unwrap();
-    let value = arg
-        .lock()
-        .expect("math.round: failed to get first argument");
-
-    if value.kind() != ValueType::Decimal && value.kind() != ValueType::Integer {
-        return mk_runtime_value(Box::new(NullValue::default()));
-    }
-
-    if value.kind() == ValueType::Decimal {
-        let decimal = cast_value::<DecimalValue>(&value).unwrap();
-        return mk_runtime_value(Box::new(DecimalValue::from(decimal.value().round())));
-    }
-
-    // only integer case left
-    return arg.clone();
+fn trunc(_context: &mut dyn NativeContext, args: Vec<Value>) -> Result<Value, InterpreterError> {
+    Ok(round_with(&args, f64::trunc))
 }
 
-fn pow(args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>) -> Arc<Mutex<Box<dyn RuntimeValue>>> {
-    if args.len() != 2 {
-        return mk_runtime_value(Box::new(NullValue::default()));
-    }
-
-    let target_val = args
-        .get(0)
-        .unwrap()
-        .lock()
-        .expect("math.pow: failed to get first argument");
-    let factor_val = args
-        .get(1)
-        .unwrap()
-        .lock()
-        .expect("math.pow: failed to get second argument");
-
-    if target_val.kind() != ValueType::Decimal && target_val.kind() != ValueType::Integer {
-        return mk_runtime_value(Box::new(NullValue::default()));
-    }
-
-    if factor_val.kind() != ValueType::Decimal && factor_val.kind() != ValueType::Integer {
-        return mk_runtime_value(Box::new(NullValue::default()));
-    }
-
-    if target_val.kind() == ValueType::Decimal || factor_val.kind() == ValueType::Decimal {
-        let target = if target_val.kind() == ValueType::Decimal {
-            cast_value::<DecimalValue>(&target_val).unwrap().value()
-        } else {
-            cast_value::<IntegerValue>(&target_val).unwrap().value() as f64
-        };
-
-        let factor = if factor_val.kind() == ValueType::Decimal {
-            cast_value::<DecimalValue>(&factor_val).unwrap().value()
-        } else {
-            cast_value::<IntegerValue>(&factor_val).unwrap().value() as f64
-        };
-
-        return mk_runtime_value(Box::new(DecimalValue::from(target.powf(factor))));
-    }
-
-    let target = cast_value::<IntegerValue>(&target_val).unwrap().value();
-    let factor = cast_value::<IntegerValue>(&factor_val).unwrap().value();
-
-    return mk_runtime_value(Box::new(IntegerValue::from(target.pow(factor as u32))));
+fn round(_context: &mut dyn NativeContext, args: Vec<Value>) -> Result<Value, InterpreterError> {
+    Ok(round_with(&args, f64::round))
 }
 
-fn sqrt(args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>) -> Arc<Mutex<Box<dyn RuntimeValue>>> {
-    if args.is_empty() {
-        return mk_runtime_value(Box::new(NullValue::default()));
-    }
-
-    let arg = args.get(0).unwrap();
-    let value = arg.lock().expect("math.sqrt: failed to get first argument");
-
-    if value.kind() != ValueType::Decimal && value.kind() != ValueType::Integer {
-        return mk_runtime_value(Box::new(NullValue::default()));
-    }
-
-    if value.kind() == ValueType::Decimal {
-        let decimal = cast_value::<DecimalValue>(&value).unwrap();
-        return mk_runtime_value(Box::new(DecimalValue::from(decimal.value().sqrt())));
-    }
-
-    if value.kind() == ValueType::Integer {
-        let integer = cast_value::<IntegerValue>(&value).unwrap();
-        return mk_runtime_value(Box::new(DecimalValue::from(
-            (integer.value() as f64).sqrt(),
-        )));
-    }
+fn pow(_context: &mut dyn NativeContext, args: Vec<Value>) -> Result<Value, InterpreterError> {
+    let (target, factor) = match args.as_slice() {
+        [Value::Integer(target), Value::Integer(factor)] if *factor >= 0 => {
+            // pow overflows like the multiplications it stands for
+            match u32::try_from(*factor)
+                .ok()
+                .and_then(|factor| target.checked_pow(factor))
+            {
+                Some(result) => return Ok(Value::from(result)),
+                None => bail!(InterpreterError::ArithmeticError(
+                    BinaryOperator::Multiply,
+                    Value::from(*target),
+                    Value::from(*factor)
+                )),
+            }
+        }
+        // negative exponents give fractions
+        [Value::Integer(target), Value::Integer(factor)] => (*target as f64, *factor as f64),
+        [Value::Decimal(target), Value::Decimal(factor)] => (*target, *factor),
+        [Value::Decimal(target), Value::Integer(factor)] => (*target, *factor as f64),
+        [Value::Integer(target), Value::Decimal(factor)] => (*target as f64, *factor),
+        _ => return Ok(Value::Null),
+    };
+
+    // finite operands must produce a finite result, like the decimal operators
+    let result = target.powf(factor);
+    if !result.is_finite() && target.is_finite() && factor.is_finite() {
+        bail!(InterpreterError::ArithmeticError(
+            BinaryOperator::Multiply,
+            args[0].clone(),
+            args[1].clone()
+        ))
+    }
+    Ok(Value::from(result))
+}
 
-    // only integer case left
-    return arg.clone();
+fn sqrt(_context: &mut dyn NativeContext, args: Vec<Value>) -> Result<Value, InterpreterError> {
+    let result = match args.first() {
+        Some(Value::Decimal(decimal)) => Value::from(decimal.sqrt()),
+        Some(Value::Integer(integer)) => Value::from((*integer as f64).sqrt()),
+        _ => Value::Null,
+    };
+    Ok(result)
 }
 
 pub fn get_math() -> HashMap<Key, Value> {
     let mut map: HashMap<Key, Value> = HashMap::new();
 
-    map.insert(
-        "PI".to_string(),
-        mk_runtime_value(Box::new(DecimalValue::from(std::f64::consts::PI))),
-    );
+    map.insert("PI".to_string(), Value::from(std::f64::consts::PI));
 
     map.insert(
         "random".to_string(),
-        mk_native_fn(
-            "math.random".to_string(),
-            Arc::new(Mutex::new(Box::new(random))),
-        ),
+        mk_native_fn("math.random".to_string(), Arc::new(random)),
     );
 
     map.insert(
         "floor".to_string(),
-        mk_native_fn(
-            "math.floor".to_string(),
-            Arc::new(Mutex::new(Box::new(floor))),
-        ),
+        mk_native_fn("math.floor".to_string(), Arc::new(floor)),
     );
 
     map.insert(
         "ceil".to_string(),
-        mk_native_fn(
-            "math.ceil".to_string(),
-            Arc::new(Mutex::new(Box::new(ceil))),
-        ),
+        mk_native_fn("math.ceil".to_string(), Arc::new(ceil)),
     );
 
     map.insert(
         "trunc".to_string(),
-        mk_native_fn(
-            "math.trunc".to_string(),
-            Arc::new(Mutex::new(Box::new(trunc))),
-        ),
+        mk_native_fn("math.trunc".to_string(), Arc::new(trunc)),
     );
 
     map.insert(
         "round".to_string(),
-        mk_native_fn(
-            "math.round".to_string(),
-            Arc::new(Mutex::new(Box::new(round))),
-        ),
+        mk_native_fn("math.round".to_string(), Arc::new(round)),
     );
 
     map.insert(
         "pow".to_string(),
-        mk_native_fn("math.pow".to_string(), Arc::new(Mutex::new(Box::new(pow)))),
+        mk_native_fn("math.pow".to_string(), Arc::new(pow)),
     );
 
     map.insert(
         "sqrt".to_string(),
-        mk_native_fn(
-            "math.sqrt".to_string(),
-            Arc::new(Mutex::new(Box::new(sqrt))),
-        ),
+        mk_native_fn("math.sqrt".to_string(), Arc::new(sqrt)),
     );
 
     map
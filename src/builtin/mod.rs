@@ -1,73 +1,59 @@
 mod array;
-mod cast_value;
 mod convert;
+mod is_instance;
 mod math;
 mod number;
 mod print;
+mod prototype;
+mod range;
 mod string;
 mod stringify;
 mod time;
 mod type_of;
 
 use crate::{
-    BoolValue, ClosureType, Environment, EnvironmentId, NativeFnValue, NullValue, ObjectValue,
-    RuntimeValue, WithFnCall, SCOPE_STATE,
+    ClosureType, Environment, EnvironmentHandle, NativeFnValue, ObjectValue, ScopeState, Value,
+    WithFnCall,
 };
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 pub use array::*;
-pub use cast_value::*;
 pub use convert::*;
+pub use is_instance::*;
 pub use math::*;
 pub use number::*;
 pub use print::*;
+pub use prototype::*;
+pub use range::*;
 pub use string::*;
 pub use stringify::*;
 pub use time::*;
 pub use type_of::*;
 
-pub fn mk_runtime_value(value: Box<dyn RuntimeValue>) -> Arc<Mutex<Box<dyn RuntimeValue>>> {
-    Arc::new(Mutex::new(value))
-}
-
-pub fn mk_native_fn(name: String, func: ClosureType) -> Arc<Mutex<Box<dyn RuntimeValue>>> {
+pub fn mk_native_fn(name: String, func: ClosureType) -> Value {
     let with_call = WithFnCall::new(func);
-    Arc::new(Mutex::new(Box::new(NativeFnValue::new(name, with_call))))
+    Value::from(NativeFnValue::new(name, with_call))
 }
 
-pub fn setup_default_environment() -> EnvironmentId {
+/// Declares the builtins in a new root environment of the given scope state
+pub fn setup_default_environment(scope_state: &mut ScopeState) -> EnvironmentHandle {
     // basic constants
     let mut environment = Environment::new(None);
     environment
-        .declare_variable(
-            "true".to_string(),
-            mk_runtime_value(Box::new(BoolValue::from(true))),
-            true,
-        )
+        .declare_variable("true".to_string(), Value::from(true), true)
         .unwrap();
     environment
-        .declare_variable(
-            "false".to_string(),
-            mk_runtime_value(Box::new(BoolValue::from(false))),
-            true,
-        )
+        .declare_variable("false".to_string(), Value::from(false), true)
         .unwrap();
     environment
-        .declare_variable(
-            "null".to_string(),
-            mk_runtime_value(Box::new(NullValue::default())),
-            true,
-        )
+        .declare_variable("null".to_string(), Value::Null, true)
         .unwrap();
 
     // native print
     environment
         .declare_variable(
             "print".to_string(),
-            mk_native_fn(
-                "print".to_string(),
-                Arc::new(Mutex::new(native_print_function)),
-            ),
+            mk_native_fn("print".to_string(), Arc::new(native_print_function)),
             true,
         )
         .unwrap();
@@ -76,7 +62,7 @@ pub fn setup_default_environment() -> EnvironmentId {
     environment
         .declare_variable(
             "time".to_string(),
-            mk_native_fn("time".to_string(), Arc::new(Mutex::new(native_get_time))),
+            mk_native_fn("time".to_string(), Arc::new(native_get_time)),
             true,
         )
         .unwrap();
@@ -85,7 +71,16 @@ pub fn setup_default_environment() -> EnvironmentId {
     environment
         .declare_variable(
             "math".to_string(),
-            mk_runtime_value(Box::new(ObjectValue::from(get_math()))),
+            Value::from(ObjectValue::from(get_math())),
+            true,
+        )
+        .unwrap();
+
+    // native range
+    environment
+        .declare_variable(
+            "range".to_string(),
+            mk_native_fn("range".to_string(), Arc::new(native_range)),
             true,
         )
         .unwrap();
@@ -93,7 +88,15 @@ pub fn setup_default_environment() -> EnvironmentId {
     environment
         .declare_variable(
             "type_of".to_string(),
-            mk_native_fn("type_of".into(), Arc::new(Mutex::new(native_type_of))),
+            mk_native_fn("type_of".into(), Arc::new(native_type_of)),
+            true,
+        )
+        .unwrap();
+
+    environment
+        .declare_variable(
+            "is_instance".to_string(),
+            mk_native_fn("is_instance".into(), Arc::new(native_is_instance)),
             true,
         )
         .unwrap();
@@ -102,15 +105,10 @@ pub fn setup_default_environment() -> EnvironmentId {
     environment
         .declare_variable(
             "String".to_string(),
-            mk_native_fn("String".into(), Arc::new(Mutex::new(native_string_convert))),
+            mk_native_fn("String".into(), Arc::new(native_string_convert)),
             true,
         )
         .unwrap();
 
-    let env_id = SCOPE_STATE
-        .lock()
-        .expect("setup_default_environment: failed to get scope state")
-        .append_environment(environment);
-
-    env_id
+    scope_state.append_environment(environment)
 }
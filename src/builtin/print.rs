@@ -1,23 +1,13 @@
-use std::sync::{Arc, Mutex};
-
-use crate::{
-    builtin::{mk_runtime_value, stringify},
-    NullValue, RuntimeValue,
-};
+use crate::{builtin::display, InterpreterError, NativeContext, Value};
 
 pub fn native_print_function(
-    args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>,
-) -> Arc<Mutex<Box<dyn RuntimeValue>>> {
-    println!(
-        "{}",
-        args.into_iter()
-            .map(|arg| {
-                let val = arg.lock().expect("native_print: failed to get argument");
-                let cloned = dyn_clone::clone_box(&**val);
-                stringify(cloned)
-            })
-            .collect::<Vec<String>>()
-            .join(" ")
-    );
-    mk_runtime_value(Box::new(NullValue::default()))
+    context: &mut dyn NativeContext,
+    args: Vec<Value>,
+) -> Result<Value, InterpreterError> {
+    let mut items = vec![];
+    for arg in &args {
+        items.push(display(context, arg)?);
+    }
+    println!("{}", items.join(" "));
+    Ok(Value::Null)
 }
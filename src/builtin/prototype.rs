@@ -0,0 +1,102 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{ClassValue, Key, NativeContext, Value};
+
+use super::{get_array_prototype, get_number_prototype, get_string_prototype, mk_native_fn};
+
+/// Names of the classes that add methods to builtin values instead of declaring a variable
+const EXTENDABLE_TYPES: [&str; 5] = ["String", "Number", "Boolean", "Object", "Array"];
+
+/// Methods of the builtin values, built once per interpreter instead of on every access.
+/// The natives take the receiver as their first argument
+pub struct Prototypes {
+    string: HashMap<Key, Value>,
+    number: HashMap<Key, Value>,
+    array: HashMap<Key, Value>,
+    /// Classes declared by scripts, by the name of the type they extend.
+    /// They belong to the interpreter rather than to an environment, so a declaration
+    /// inside a function or an imported module extends the values everywhere once it runs
+    extensions: HashMap<Key, Vec<Arc<ClassValue>>>,
+}
+
+impl Prototypes {
+    pub fn new() -> Self {
+        Self {
+            string: get_string_prototype(),
+            number: get_number_prototype(),
+            array: get_array_prototype(),
+            extensions: HashMap::new(),
+        }
+    }
+
+    /// Whether a class with the name extends builtin values, e.g. `class String { ... }`
+    pub fn is_extendable(name: &str) -> bool {
+        EXTENDABLE_TYPES.contains(&name)
+    }
+
+    /// Makes the methods of the class available on the values of the type it is named after,
+    /// for the rest of the run and regardless of where the class was declared
+    pub fn extend(&mut self, class: Arc<ClassValue>) {
+        self.extensions
+            .entry(class.name.clone())
+            .or_default()
+            .push(class);
+    }
+
+    /// Searches the classes that extend the type of the value, the latest declaration first
+    pub fn find_extension<T>(
+        &self,
+        value: &Value,
+        find: impl Fn(&Arc<ClassValue>) -> Option<T>,
+    ) -> Option<T> {
+        let name = match value {
+            Value::String(_) => "String",
+            Value::Integer(_) | Value::Decimal(_) => "Number",
+            Value::Boolean(_) => "Boolean",
+            Value::Object(_) => "Object",
+            Value::Array(_) => "Array",
+            _ => return None,
+        };
+        self.extensions.get(name)?.iter().rev().find_map(find)
+    }
+
+    /// Native method of the value, it has to be called with the value as the first argument
+    pub fn get_method(&self, value: &Value, key: &str) -> Option<&Value> {
+        let methods = match value {
+            Value::String(_) => &self.string,
+            Value::Integer(_) | Value::Decimal(_) => &self.number,
+            Value::Array(_) => &self.array,
+            _ => return None,
+        };
+        methods.get(key)
+    }
+
+    /// Property of the value, methods are bound to the value so they can be called later
+    pub fn get_property(&self, value: &Value, key: &str) -> Option<Value> {
+        match (value, key) {
+            (Value::String(string), "length") => {
+                return Some(Value::from(string.chars().count() as isize))
+            }
+            (Value::Array(array), "length") => return Some(Value::from(array.len() as isize)),
+            _ => {}
+        }
+        let method = match self.get_method(value, key)? {
+            Value::NativeFn(method) => method.clone(),
+            method => return Some(method.clone()),
+        };
+        let receiver = value.clone();
+        Some(mk_native_fn(
+            method.name.clone(),
+            Arc::new(move |context: &mut dyn NativeContext, args: Vec<Value>| {
+                let args = [vec![receiver.clone()], args].concat();
+                method.callee().run(context, args)
+            }),
+        ))
+    }
+}
+
+impl Default for Prototypes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,35 @@
+use crate::{macros::bail, ArrayValue, InterpreterError, NativeContext, Value};
+
+/// Longest range the builtin builds, the items are created up front
+pub const MAX_RANGE_LENGTH: usize = 1_000_000;
+
+/// range(end) or range(start, end), end is exclusive
+pub fn native_range(
+    _context: &mut dyn NativeContext,
+    args: Vec<Value>,
+) -> Result<Value, InterpreterError> {
+    let mut bounds: Vec<isize> = vec![];
+    for arg in &args {
+        match arg {
+            Value::Integer(value) => bounds.push(*value),
+            arg => bail!(InterpreterError::InvalidValue(
+                arg.clone(),
+                "integer".to_string()
+            )),
+        }
+    }
+
+    let (start, end) = match bounds.as_slice() {
+        [end] => (0, *end),
+        [start, end] => (*start, *end),
+        _ => bail!(InterpreterError::InvalidParameterCount(2, args.len())),
+    };
+
+    if end.saturating_sub(start) > MAX_RANGE_LENGTH as isize {
+        bail!(InterpreterError::RangeTooLarge(start, end))
+    }
+
+    let items = (start..end).map(Value::from).collect::<Vec<Value>>();
+
+    Ok(Value::from(ArrayValue::from(items)))
+}
@@ -1,353 +1,228 @@
-use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
-};
+use std::{collections::HashMap, sync::Arc};
 
 use crate::{
-    cast_value, convert_to_string, ArrayValue, ClosureType, IntegerValue, Key, NullValue,
-    ObjectValue, RuntimeValue, StringValue, Value, ValueType,
+    convert_to_string, macros::bail, ArrayValue, InterpreterError, Key, NativeContext, Value,
 };
 
-use super::{mk_native_fn, mk_runtime_value};
-
-pub fn get_char(value: StringValue) -> ClosureType {
-    Arc::new(Mutex::new(
-        move |args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>| {
-            if args.len() < 1 {
-                return mk_runtime_value(Box::new(NullValue::default()));
-            }
-
-            let arg = args
-                .get(0)
-                .unwrap()
-                .lock()
-                .expect("string.get_char: failed to get argument");
-
-            if arg.kind() != ValueType::Integer {
-                return mk_runtime_value(Box::new(NullValue::default()));
-            }
+use super::mk_native_fn;
 
-            let index = cast_value::<IntegerValue>(&arg).unwrap();
-
-            if index.value() < 0 {
-                return mk_runtime_value(Box::new(NullValue::default()));
-            }
-
-            if value.value().chars().count() <= index.value() as usize {
-                return mk_runtime_value(Box::new(NullValue::default()));
-            }
+/// Splits the arguments of a string method into the receiver and the arguments of the call
+fn split_receiver(args: &[Value]) -> Option<(&Arc<str>, &[Value])> {
+    match args.split_first() {
+        Some((Value::String(value), args)) => Some((value, args)),
+        _ => None,
+    }
+}
 
-            let char = value.value().chars().nth(index.value() as usize).unwrap();
+fn get_char(_context: &mut dyn NativeContext, args: Vec<Value>) -> Result<Value, InterpreterError> {
+    let (value, args) = match split_receiver(&args) {
+        Some(receiver) => receiver,
+        None => return Ok(Value::Null),
+    };
+
+    let index = match args.first() {
+        Some(Value::Integer(index)) if *index >= 0 => *index as usize,
+        _ => return Ok(Value::Null),
+    };
+
+    let result = match value.chars().nth(index) {
+        Some(char) => Value::from(char.to_string()),
+        None => Value::Null,
+    };
+    Ok(result)
+}
 
-            mk_runtime_value(Box::new(StringValue::from(char.to_string())))
-        },
-    ))
+fn concat(context: &mut dyn NativeContext, args: Vec<Value>) -> Result<Value, InterpreterError> {
+    let (value, args) = match split_receiver(&args) {
+        Some(receiver) => receiver,
+        None => return Ok(Value::Null),
+    };
+
+    let mut result = String::from(&**value);
+    for arg in args {
+        match arg {
+            Value::String(str_value) => result.push_str(str_value),
+            // instances that define `__str__` are concatenated by it
+            Value::ClassInstance(_) => match context.call_str_method(arg)? {
+                Some(display) => result.push_str(&display),
+                None => bail!(InterpreterError::InvalidValue(
+                    arg.clone(),
+                    "string".to_string()
+                )),
+            },
+            arg => bail!(InterpreterError::InvalidValue(
+                arg.clone(),
+                "string".to_string()
+            )),
+        }
+    }
+
+    Ok(Value::from(result))
 }
 
-pub fn concat(value: StringValue) -> ClosureType {
-    Arc::new(Mutex::new(
-        move |args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>| {
-            let mut result = String::from(value.value());
-            for arg in args {
-                let value = arg.lock().expect("string.concat: failed to get argument");
-                if value.kind() != ValueType::String {
-                    // TODO: raise type error
-                    continue;
-                }
-                let str_value = cast_value::<StringValue>(&value).unwrap();
-                result.push_str(&str_value.value());
-            }
-
-            mk_runtime_value(Box::new(StringValue::from(result)))
-        },
-    ))
+fn substr(_context: &mut dyn NativeContext, args: Vec<Value>) -> Result<Value, InterpreterError> {
+    let (value, args) = match split_receiver(&args) {
+        Some(receiver) => receiver,
+        None => return Ok(Value::Null),
+    };
+
+    let skip = match args.first() {
+        Some(Value::Integer(skip)) if *skip >= 0 => *skip,
+        _ => return Ok(Value::Null),
+    };
+
+    let length = value.chars().count() as isize;
+
+    let mut take = match args.get(1) {
+        None => length - skip,
+        Some(Value::Integer(take)) => *take,
+        Some(_) => return Ok(Value::Null),
+    };
+
+    if take < 0 {
+        if take.abs() >= length - skip {
+            return Ok(Value::Null);
+        }
+        take += length - skip;
+    }
+
+    let result: String = value
+        .chars()
+        .skip(skip as usize)
+        .take(take as usize)
+        .collect();
+
+    Ok(Value::from(result))
 }
 
-pub fn substr(value: StringValue) -> ClosureType {
-    Arc::new(Mutex::new(
-        move |args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>| {
-            if args.len() < 1 {
-                return mk_runtime_value(Box::new(NullValue::default()));
-            }
-
-            let skip_val = args
-                .get(0)
-                .unwrap()
-                .lock()
-                .expect("string.substr: failed to get argument");
-
-            if skip_val.kind() != ValueType::Integer {
-                return mk_runtime_value(Box::new(NullValue::default()));
-            }
-
-            let skip = cast_value::<IntegerValue>(&skip_val).unwrap();
-
-            if skip.value() < 0 {
-                return mk_runtime_value(Box::new(NullValue::default()));
-            }
-
-            let take_val = if args.len() < 2 {
-                Box::new(IntegerValue::from(
-                    value.value().chars().count() as isize - skip.value() as isize,
-                ))
-            } else {
-                dyn_clone::clone_box(
-                    &**args
-                        .get(1)
-                        .unwrap()
-                        .lock()
-                        .expect("string.substr: failed to get second argument"),
-                )
-            };
-
-            if take_val.kind() != ValueType::Integer {
-                return mk_runtime_value(Box::new(NullValue::default()));
-            }
-
-            let mut take = cast_value::<IntegerValue>(&take_val).unwrap();
-
-            if take.value() < 0 {
-                if take.value().abs() >= value.value().chars().count() as isize - skip.value() {
-                    return mk_runtime_value(Box::new(NullValue::default()));
-                }
-                take = Box::new(IntegerValue::from(
-                    value.value().chars().count() as isize - skip.value() + take.value(),
-                ))
-            }
-
-            let result: String = value
-                .value()
-                .chars()
-                .skip(skip.value() as usize)
-                .take(take.value() as usize)
-                .collect();
-
-            mk_runtime_value(Box::new(StringValue::from(result)))
-        },
-    ))
+/// Applies the conversion to the receiver, the other arguments are ignored
+fn convert_with(args: &[Value], convert: fn(&str) -> String) -> Result<Value, InterpreterError> {
+    let result = match split_receiver(args) {
+        Some((value, _)) => Value::from(convert(value)),
+        None => Value::Null,
+    };
+    Ok(result)
 }
 
-pub fn upper(value: StringValue) -> ClosureType {
-    Arc::new(Mutex::new(
-        move |_args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>| {
-            mk_runtime_value(Box::new(StringValue::from(value.value().to_uppercase())))
-        },
-    ))
+fn upper(_context: &mut dyn NativeContext, args: Vec<Value>) -> Result<Value, InterpreterError> {
+    convert_with(&args, str::to_uppercase)
 }
 
-pub fn lower(value: StringValue) -> ClosureType {
-    Arc::new(Mutex::new(
-        move |_args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>| {
-            mk_runtime_value(Box::new(StringValue::from(value.value().to_lowercase())))
-        },
-    ))
+fn lower(_context: &mut dyn NativeContext, args: Vec<Value>) -> Result<Value, InterpreterError> {
+    convert_with(&args, str::to_lowercase)
 }
 
-pub fn trim(value: StringValue) -> ClosureType {
-    Arc::new(Mutex::new(
-        move |_args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>| {
-            mk_runtime_value(Box::new(StringValue::from(
-                value.value().trim().to_string(),
-            )))
-        },
-    ))
+fn trim(_context: &mut dyn NativeContext, args: Vec<Value>) -> Result<Value, InterpreterError> {
+    convert_with(&args, |value| value.trim().to_string())
 }
 
-pub fn trim_start(value: StringValue) -> ClosureType {
-    Arc::new(Mutex::new(
-        move |_args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>| {
-            mk_runtime_value(Box::new(StringValue::from(
-                value.value().trim_start().to_string(),
-            )))
-        },
-    ))
+fn trim_start(
+    _context: &mut dyn NativeContext,
+    args: Vec<Value>,
+) -> Result<Value, InterpreterError> {
+    convert_with(&args, |value| value.trim_start().to_string())
 }
 
-pub fn trim_end(value: StringValue) -> ClosureType {
-    Arc::new(Mutex::new(
-        move |_args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>| {
-            mk_runtime_value(Box::new(StringValue::from(
-                value.value().trim_end().to_string(),
-            )))
-        },
-    ))
+fn trim_end(_context: &mut dyn NativeContext, args: Vec<Value>) -> Result<Value, InterpreterError> {
+    convert_with(&args, |value| value.trim_end().to_string())
 }
 
-pub fn replace(value: StringValue) -> ClosureType {
-    Arc::new(Mutex::new(
-        move |args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>| {
-            if args.len() != 2 {
-                return mk_runtime_value(Box::new(NullValue::default()));
-            }
-
-            let search_val = args
-                .get(0)
-                .unwrap()
-                .lock()
-                .expect("string.replace: failed to get argument");
-
-            if search_val.kind() != ValueType::String {
-                return mk_runtime_value(Box::new(NullValue::default()));
-            }
-
-            let replace_val = args
-                .get(1)
-                .unwrap()
-                .lock()
-                .expect("string.replace: failed to get second argument");
-
-            if replace_val.kind() != ValueType::String {
-                return mk_runtime_value(Box::new(NullValue::default()));
-            }
-
-            let search = cast_value::<StringValue>(&search_val).unwrap();
-            let replace = cast_value::<StringValue>(&replace_val).unwrap();
-
-            mk_runtime_value(Box::new(StringValue::from(
-                value.value().replace(&search.value(), &replace.value()),
-            )))
-        },
-    ))
+fn replace(_context: &mut dyn NativeContext, args: Vec<Value>) -> Result<Value, InterpreterError> {
+    let result = match args.as_slice() {
+        [Value::String(value), Value::String(search), Value::String(replace)] => {
+            Value::from(value.replace(&**search, replace))
+        }
+        _ => Value::Null,
+    };
+    Ok(result)
 }
 
-pub fn split(value: StringValue) -> ClosureType {
-    Arc::new(Mutex::new(
-        move |args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>| {
-            if args.len() != 1 {
-                return mk_runtime_value(Box::new(NullValue::default()));
-            }
-
-            let split_val = args
-                .get(0)
-                .unwrap()
-                .lock()
-                .expect("string.split: failed to get argument");
-
-            if split_val.kind() != ValueType::String {
-                return mk_runtime_value(Box::new(NullValue::default()));
-            }
-
-            let split = cast_value::<StringValue>(&split_val).unwrap();
-
-            let parts = value
-                .value()
-                .split(&split.value())
-                .map(|x| x.to_string())
-                .collect::<Vec<String>>();
-
-            let arr: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>> = parts
-                .into_iter()
-                .map(|x| mk_runtime_value(Box::new(StringValue::from(x))))
-                .collect();
-
-            mk_runtime_value(Box::new(ArrayValue::from(arr)))
-        },
-    ))
+fn split(_context: &mut dyn NativeContext, args: Vec<Value>) -> Result<Value, InterpreterError> {
+    let (value, split) = match args.as_slice() {
+        [Value::String(value), Value::String(split)] => (value, split),
+        _ => return Ok(Value::Null),
+    };
+
+    let parts = value
+        .split(&**split)
+        .map(Value::from)
+        .collect::<Vec<Value>>();
+
+    Ok(Value::from(ArrayValue::from(parts)))
 }
 
-pub fn join(value: StringValue) -> ClosureType {
-    Arc::new(Mutex::new(
-        move |args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>| {
-            if args.len() != 1 {
-                return mk_runtime_value(Box::new(NullValue::default()));
-            }
-
-            let join_val = args
-                .get(0)
-                .unwrap()
-                .lock()
-                .expect("string.join: failed to get argument");
-
-            if join_val.kind() != ValueType::Array {
-                return mk_runtime_value(Box::new(NullValue::default()));
-            }
-
-            let array = cast_value::<ArrayValue>(&join_val).unwrap();
-
-            let joined = array
-                .value()
-                .iter()
-                .map(|item| {
-                    convert_to_string(&item.lock().expect("string.join: failed to get array item"))
-                })
-                .collect::<Vec<String>>()
-                .join(&value.value());
-
-            mk_runtime_value(Box::new(StringValue::from(joined)))
-        },
-    ))
+fn join(context: &mut dyn NativeContext, args: Vec<Value>) -> Result<Value, InterpreterError> {
+    let (value, array) = match args.as_slice() {
+        [Value::String(value), Value::Array(array)] => (value, array),
+        _ => return Ok(Value::Null),
+    };
+
+    let mut items = vec![];
+    for item in array.value() {
+        items.push(convert_to_string(context, &item)?);
+    }
+
+    Ok(Value::from(items.join(value)))
 }
 
-pub fn get_string_object(string_value: &StringValue) -> Box<ObjectValue> {
+/// Methods shared by every string, see `Prototypes`
+pub fn get_string_prototype() -> HashMap<Key, Value> {
     let mut map: HashMap<Key, Value> = HashMap::new();
 
     map.insert(
         "get".to_string(),
-        mk_native_fn("string.get".to_string(), get_char(string_value.clone())),
+        mk_native_fn("string.get".to_string(), Arc::new(get_char)),
     );
 
     map.insert(
         "concat".to_string(),
-        mk_native_fn("string.concat".to_string(), concat(string_value.clone())),
+        mk_native_fn("string.concat".to_string(), Arc::new(concat)),
     );
 
     map.insert(
         "substr".to_string(),
-        mk_native_fn("string.substr".to_string(), substr(string_value.clone())),
+        mk_native_fn("string.substr".to_string(), Arc::new(substr)),
     );
 
     map.insert(
         "upper".to_string(),
-        mk_native_fn("string.upper".to_string(), upper(string_value.clone())),
+        mk_native_fn("string.upper".to_string(), Arc::new(upper)),
     );
 
     map.insert(
         "lower".to_string(),
-        mk_native_fn("string.lower".to_string(), lower(string_value.clone())),
+        mk_native_fn("string.lower".to_string(), Arc::new(lower)),
     );
 
     map.insert(
         "replace".to_string(),
-        mk_native_fn("string.replace".to_string(), replace(string_value.clone())),
+        mk_native_fn("string.replace".to_string(), Arc::new(replace)),
     );
 
     map.insert(
         "trim".to_string(),
-        mk_native_fn("string.trim".to_string(), trim(string_value.clone())),
+        mk_native_fn("string.trim".to_string(), Arc::new(trim)),
     );
 
     map.insert(
         "trim_start".to_string(),
-        mk_native_fn(
-            "string.trim_start".to_string(),
-            trim_start(string_value.clone()),
-        ),
+        mk_native_fn("string.trim_start".to_string(), Arc::new(trim_start)),
     );
 
     map.insert(
         "trim_end".to_string(),
-        mk_native_fn(
-            "string.trim_end".to_string(),
-            trim_end(string_value.clone()),
-        ),
+        mk_native_fn("string.trim_end".to_string(), Arc::new(trim_end)),
     );
 
     map.insert(
         "split".to_string(),
-        mk_native_fn("string.split".to_string(), split(string_value.clone())),
+        mk_native_fn("string.split".to_string(), Arc::new(split)),
     );
 
     map.insert(
         "join".to_string(),
-        mk_native_fn("string.join".to_string(), join(string_value.clone())),
-    );
-
-    map.insert(
-        "length".to_string(),
-        mk_runtime_value(Box::new(IntegerValue::from(
-            string_value.value().chars().count() as isize,
-        ))),
+        mk_native_fn("string.join".to_string(), Arc::new(join)),
     );
 
-    Box::new(ObjectValue::from(map))
+    map
 }
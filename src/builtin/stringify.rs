@@ -3,23 +3,18 @@ use std::{
     fmt::{Debug, Formatter},
 };
 
-use crate::{
-    ArrayValue, BoolValue, ClassInstanceValue, ClassValue, DecimalValue, FunctionValue,
-    IntegerValue, NativeFnValue, ObjectValue, RuntimeValue, StringValue, ValueType,
-};
-
-use super::cast_value;
+use crate::{InterpreterError, Key, NativeContext, Value};
 
 const ARRAY_MAX_ITEMS: usize = 10;
 const OBJECT_MAX_ITEMS: usize = 30;
 
-struct DebugHashMap<'a>(&'a HashMap<String, String>, isize);
+struct DebugMap<'a>(&'a [(String, String)], isize);
 
-impl<'a> Debug for DebugHashMap<'a> {
+impl<'a> Debug for DebugMap<'a> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let mut debug_map = f.debug_map();
         for (key, value) in self.0 {
-            debug_map.entry(&key, &DebugValue(&value));
+            debug_map.entry(&key, &DebugValue(value));
         }
         if self.1 > 0 {
             debug_map.entry(
@@ -39,106 +34,132 @@ impl<'a> Debug for DebugValue<'a> {
     }
 }
 
-pub fn stringify(value: Box<dyn RuntimeValue>) -> String {
-    match value.kind() {
-        ValueType::Null => "null".to_string(),
-        ValueType::Boolean => {
-            let boolean = cast_value::<BoolValue>(&value).unwrap();
-            boolean.value().to_string()
-        }
-        ValueType::Decimal => cast_value::<DecimalValue>(&value)
-            .unwrap()
-            .value()
-            .to_string(),
-        ValueType::Integer => cast_value::<IntegerValue>(&value)
-            .unwrap()
-            .value()
-            .to_string(),
-        ValueType::Function => {
-            let function = cast_value::<FunctionValue>(&value).unwrap();
+/// Stringifies instances by their fields, for output that doesn't run scripts, e.g. errors
+struct NoContext;
+
+impl NativeContext for NoContext {
+    fn call_str_method(&mut self, _instance: &Value) -> Result<Option<String>, InterpreterError> {
+        Ok(None)
+    }
+}
+
+pub fn stringify(value: &Value) -> String {
+    // only `__str__` methods can fail
+    display(&mut NoContext, value).unwrap_or_default()
+}
+
+/// Same as `stringify`, but instances that define `__str__` are stringified by it
+pub fn display(context: &mut dyn NativeContext, value: &Value) -> Result<String, InterpreterError> {
+    stringify_with(context, value, &mut vec![])
+}
+
+/// `parents` holds the arrays, objects and instances that contain the value,
+/// a value that contains itself is stringified as `[Circular]` the second time
+fn stringify_with(
+    context: &mut dyn NativeContext,
+    value: &Value,
+    parents: &mut Vec<usize>,
+) -> Result<String, InterpreterError> {
+    let id = match value {
+        Value::Array(array) => array.id(),
+        Value::Object(object) => object.id(),
+        Value::ClassInstance(instance) => instance.fields().id(),
+        value => return stringify_value(context, value, parents),
+    };
+    if parents.contains(&id) {
+        return Ok("[Circular]".to_string());
+    }
+    parents.push(id);
+    let result = stringify_value(context, value, parents);
+    parents.pop();
+    result
+}
+
+fn stringify_value(
+    context: &mut dyn NativeContext,
+    value: &Value,
+    parents: &mut Vec<usize>,
+) -> Result<String, InterpreterError> {
+    let result = match value {
+        Value::Null => "null".to_string(),
+        Value::Boolean(boolean) => boolean.to_string(),
+        Value::Decimal(decimal) => decimal.to_string(),
+        Value::Integer(integer) => integer.to_string(),
+        Value::Function(function) => {
             format!(
                 "<function {}({})>",
                 function.name,
                 function
                     .parameters
-                    .clone()
-                    .into_iter()
-                    .map(|x| x.name)
+                    .iter()
+                    .map(|x| x.name.clone())
                     .collect::<Vec<String>>()
                     .join(", ")
             )
         }
-        ValueType::NativeFn => {
-            let function = cast_value::<NativeFnValue>(&value).unwrap();
-            format!("<native-function {}>", function.name,)
-        }
-        ValueType::String => {
-            let string = cast_value::<StringValue>(&value).unwrap();
-            format!("{}", string.value())
+        Value::Closure(closure) => {
+            format!(
+                "<function {}({})>",
+                closure.name(),
+                closure
+                    .function
+                    .parameters
+                    .iter()
+                    .map(|x| x.name.clone())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )
         }
-        ValueType::Array => {
-            let array = cast_value::<ArrayValue>(&value).unwrap();
+        Value::NativeFn(function) => format!("<native-function {}>", function.name,),
+        Value::String(string) => string.to_string(),
+        Value::Array(array) => {
+            let items = array.value();
+            let mut strings = vec![];
+            for item in &items {
+                strings.push(match item {
+                    Value::String(string) => format!("\"{}\"", string),
+                    _ => stringify_with(context, item, parents)?,
+                });
+            }
             format!(
                 "[{}{}]",
-                array
-                    .value()
-                    .into_iter()
-                    .map(|x| {
-                        if x.lock()
-                            .expect("stringify: failed to get array item")
-                            .kind()
-                            == ValueType::String
-                        {
-                            format!(
-                                "\"{}\"",
-                                stringify(dyn_clone::clone_box(
-                                    &**x.lock()
-                                        .expect("stringify: failed to get string array item")
-                                ))
-                            )
-                        } else {
-                            stringify(dyn_clone::clone_box(
-                                &**x.lock()
-                                    .expect("stringify: failed to stringify other type array item"),
-                            ))
-                        }
-                    })
-                    .collect::<Vec<String>>()
-                    .join(", "),
-                if array.value().len() > ARRAY_MAX_ITEMS {
-                    format!(", ...more {} items", array.value().len() - ARRAY_MAX_ITEMS)
+                strings.join(", "),
+                if items.len() > ARRAY_MAX_ITEMS {
+                    format!(", ...more {} items", items.len() - ARRAY_MAX_ITEMS)
                 } else {
                     "".to_string()
                 }
             )
         }
-        ValueType::Object => {
-            let object = cast_value::<ObjectValue>(&value).unwrap();
-            let mut map: HashMap<String, String> = HashMap::new();
-            for (key, value) in object.map().iter().take(OBJECT_MAX_ITEMS) {
-                map.insert(
-                    key.clone(),
-                    stringify(dyn_clone::clone_box(
-                        &**value.lock().expect("stringify: failed to get object value"),
-                    )),
-                );
-            }
+        Value::Object(object) => stringify_map(context, object.map(), parents)?,
+        Value::Class(class) => format!("<class {}>", class.name),
+        Value::ClassInstance(instance) => match context.call_str_method(value)? {
+            Some(display) => display,
+            None => format!(
+                "{} {}",
+                instance.class_name(),
+                stringify_map(context, instance.map(), parents)?
+            ),
+        },
+    };
+    Ok(result)
+}
 
-            format!(
-                "{:#?}",
-                DebugHashMap(
-                    &map,
-                    object.map().len() as isize - OBJECT_MAX_ITEMS as isize
-                )
-            )
-        }
-        ValueType::Class => {
-            let class = cast_value::<ClassValue>(&value).unwrap();
-            format!("<class {}>", class.name)
-        }
-        ValueType::ClassInstance => {
-            let class = cast_value::<ClassInstanceValue>(&value).unwrap();
-            format!("<class_instance of {}>", class.class_name())
-        }
+fn stringify_map(
+    context: &mut dyn NativeContext,
+    object: HashMap<Key, Value>,
+    parents: &mut Vec<usize>,
+) -> Result<String, InterpreterError> {
+    let mut keys = object.keys().collect::<Vec<&Key>>();
+    // hash map order is random, print the keys in the order for-in goes through them
+    keys.sort();
+    let mut entries: Vec<(String, String)> = vec![];
+    for key in keys.into_iter().take(OBJECT_MAX_ITEMS) {
+        entries.push((key.clone(), stringify_with(context, &object[key], parents)?));
     }
+
+    Ok(format!(
+        "{:#?}",
+        DebugMap(&entries, object.len() as isize - OBJECT_MAX_ITEMS as isize)
+    ))
 }
@@ -1,33 +1,25 @@
-use std::sync::{Arc, Mutex};
-
-use crate::{NullValue, RuntimeValue, StringValue, ValueType};
-
-use super::mk_runtime_value;
+use crate::{InterpreterError, NativeContext, Value};
 
 pub fn native_type_of(
-    args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>,
-) -> Arc<Mutex<Box<dyn RuntimeValue>>> {
-    if args.is_empty() {
-        return mk_runtime_value(Box::new(NullValue::default()));
-    }
-
-    let arg = args
-        .get(0)
-        .unwrap()
-        .lock()
-        .expect("type_of: failed to get argument");
+    _context: &mut dyn NativeContext,
+    args: Vec<Value>,
+) -> Result<Value, InterpreterError> {
+    let arg = match args.first() {
+        Some(arg) => arg,
+        None => return Ok(Value::Null),
+    };
 
-    let value_type: String = match arg.kind() {
-        ValueType::Array => "array".into(),
-        ValueType::Boolean => "boolean".into(),
-        ValueType::Decimal | ValueType::Integer => "number".into(),
-        ValueType::Function | ValueType::NativeFn => "function".into(),
-        ValueType::Null => "null".into(),
-        ValueType::Object => "object".into(),
-        ValueType::String => "string".into(),
-        ValueType::Class => "class".into(),
-        ValueType::ClassInstance => "object".into(),
+    let value_type: String = match arg {
+        Value::Array(_) => "array".into(),
+        Value::Boolean(_) => "boolean".into(),
+        Value::Decimal(_) | Value::Integer(_) => "number".into(),
+        Value::Function(_) | Value::Closure(_) | Value::NativeFn(_) => "function".into(),
+        Value::Null => "null".into(),
+        Value::Object(_) => "object".into(),
+        Value::String(_) => "string".into(),
+        Value::Class(_) => "class".into(),
+        Value::ClassInstance(instance) => instance.class_name(),
     };
 
-    mk_runtime_value(Box::new(StringValue::from(value_type)))
+    Ok(Value::from(value_type))
 }
@@ -0,0 +1,137 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use crate::{Cell, Value};
+
+use super::{EnvironmentHandle, EnvironmentId, ScopeState};
+
+pub const MIN_COLLECTION_THRESHOLD: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Node {
+    Environment(EnvironmentId),
+    /// Value or collection behind an `Arc`, identified by its address
+    Shared(usize),
+}
+
+/// Builds the graph of references between environments and shared values,
+/// values report what they reference in `Value::trace`
+#[derive(Default)]
+pub struct Tracer {
+    current: Option<Node>,
+    edges: HashMap<Node, Vec<Node>>,
+    /// References found inside of the scope state
+    internal: HashMap<Node, usize>,
+    /// All references, including the ones held by the running interpreter
+    total: HashMap<Node, usize>,
+}
+
+impl Tracer {
+    pub fn trace_value(&mut self, value: &Value) {
+        value.trace(self);
+    }
+
+    pub fn trace_shared<T>(&mut self, shared: &Arc<T>, trace: impl FnOnce(&T, &mut Tracer)) {
+        let node = Node::Shared(Arc::as_ptr(shared) as usize);
+        if !self.reference(node, Arc::strong_count(shared)) {
+            return;
+        }
+        let parent = self.current.replace(node);
+        trace(shared, self);
+        self.current = parent;
+    }
+
+    pub fn trace_locked<T>(&mut self, shared: &Arc<Mutex<T>>, trace: impl FnOnce(&T, &mut Tracer)) {
+        self.trace_shared(shared, |shared, tracer| {
+            // a locked value is in use, so whatever it references counts as held by the interpreter
+            if let Ok(inner) = shared.try_lock() {
+                trace(&inner, tracer);
+            }
+        });
+    }
+
+    pub fn trace_environment(&mut self, handle: &EnvironmentHandle) {
+        self.reference(Node::Environment(handle.id()), handle.references());
+    }
+
+    /// Returns whether the node is seen for the first time
+    fn reference(&mut self, node: Node, total: usize) -> bool {
+        if let Some(current) = self.current {
+            self.edges.entry(current).or_default().push(node);
+        }
+        *self.internal.entry(node).or_default() += 1;
+        self.total.insert(node, total).is_none()
+    }
+
+    /// Nodes referenced from outside of the scope state and everything reachable from them
+    fn reachable(&self) -> HashSet<Node> {
+        let mut queue = self
+            .total
+            .iter()
+            .filter(|(node, total)| **total > self.internal.get(node).copied().unwrap_or(0))
+            .map(|(node, _)| *node)
+            .collect::<VecDeque<Node>>();
+        let mut reachable = queue.iter().copied().collect::<HashSet<Node>>();
+        while let Some(node) = queue.pop_front() {
+            for edge in self.edges.get(&node).into_iter().flatten() {
+                if reachable.insert(*edge) {
+                    queue.push_back(*edge);
+                }
+            }
+        }
+        reachable
+    }
+}
+
+impl ScopeState {
+    /// Removes the environments that can no longer be used. Cycles, like a function
+    /// stored in the environment it captures, are only kept if referenced from outside
+    pub fn collect_garbage(&mut self) {
+        let mut tracer = Tracer::default();
+        for (id, environment) in &self.scopes {
+            let node = Node::Environment(*id);
+            tracer.total.insert(node, environment.handle.references());
+            if let Some(parent) = environment.parent {
+                // the handle children keep of their parent is a reference inside of the scope state
+                let parent = Node::Environment(parent);
+                tracer.edges.entry(node).or_default().push(parent);
+                *tracer.internal.entry(parent).or_default() += 1;
+            }
+            tracer.current = Some(node);
+            for value in environment.variables.values() {
+                tracer.trace_value(value);
+            }
+        }
+
+        let reachable = tracer.reachable();
+        self.scopes
+            .retain(|id, _| reachable.contains(&Node::Environment(*id)));
+    }
+}
+
+/// Empties the cells of the vm that are only referenced from each other, like a local
+/// function that captures itself. Cells that nothing else references are removed from the list
+pub fn collect_cells(cells: &mut Vec<Cell>) {
+    cells.retain(|cell| Arc::strong_count(cell) > 1);
+    // the reference held by the list counts as internal
+    let mut tracer = Tracer::default();
+    for cell in cells.iter() {
+        tracer.trace_locked(cell, |value, tracer| {
+            if let Some(value) = value {
+                tracer.trace_value(value);
+            }
+        });
+    }
+
+    let reachable = tracer.reachable();
+    for cell in cells.iter() {
+        if !reachable.contains(&Node::Shared(Arc::as_ptr(cell) as usize)) {
+            if let Ok(mut value) = cell.try_lock() {
+                *value = None;
+            }
+        }
+    }
+    cells.retain(|cell| Arc::strong_count(cell) > 1);
+}
@@ -0,0 +1,48 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::{BuildHasherDefault, Hasher},
+};
+
+/// Maps of environments and variables, hashed on every variable access
+pub type FastHashMap<K, V> = HashMap<K, V, BuildHasherDefault<FastHasher>>;
+
+pub type FastHashSet<T> = HashSet<T, BuildHasherDefault<FastHasher>>;
+
+/// Multiplicative hash of 8 bytes at a time. The keys are environment ids and names
+/// from the scripts, so the flooding protection of the default hasher isn't needed
+#[derive(Default, Clone, Copy)]
+pub struct FastHasher(u64);
+
+impl FastHasher {
+    fn add(&mut self, word: u64) {
+        self.0 = (self.0.rotate_left(5) ^ word).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+}
+
+impl Hasher for FastHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            self.add(u64::from_le_bytes(chunk.try_into().unwrap()));
+        }
+        for byte in chunks.remainder() {
+            self.add(*byte as u64);
+        }
+    }
+
+    fn write_u8(&mut self, value: u8) {
+        self.add(value as u64);
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.add(value);
+    }
+
+    fn write_usize(&mut self, value: usize) {
+        self.add(value as u64);
+    }
+}
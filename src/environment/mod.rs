@@ -1,25 +1,64 @@
 use core::fmt;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::hash_map::Entry,
     fmt::{Debug, Formatter},
-    sync::{Arc, Mutex},
+    sync::Arc,
 };
 
-use crate::{macros::bail, InterpreterError, RuntimeValue};
+use serde::{Serialize, Serializer};
+
+use crate::{macros::bail, InterpreterError, Value};
+
+mod gc;
+mod hash;
+
+pub use gc::*;
+pub use hash::*;
 
 pub type EnvironmentId = u64;
 
+/// Keeps the environment from being collected for as long as it is held,
+/// running calls and values that capture their declaration scope hold one
+#[derive(Clone, Default)]
+pub struct EnvironmentHandle(Arc<EnvironmentId>);
+
+impl EnvironmentHandle {
+    pub fn id(&self) -> EnvironmentId {
+        *self.0
+    }
+
+    /// Handles other than the one of the environment itself, including the ones of its children
+    fn references(&self) -> usize {
+        Arc::strong_count(&self.0) - 1
+    }
+}
+
+impl Debug for EnvironmentHandle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.id())
+    }
+}
+
+impl Serialize for EnvironmentHandle {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.id())
+    }
+}
+
 #[derive(Debug)]
 pub struct ScopeState {
-    pub scopes: HashMap<EnvironmentId, Environment>,
+    pub scopes: FastHashMap<EnvironmentId, Environment>,
     pub last_generated_id: EnvironmentId,
+    /// Number of environments that triggers the next collection
+    collection_threshold: usize,
 }
 
 impl ScopeState {
     pub fn new() -> Self {
         Self {
-            scopes: HashMap::new(),
+            scopes: FastHashMap::default(),
             last_generated_id: 0,
+            collection_threshold: MIN_COLLECTION_THRESHOLD,
         }
     }
 
@@ -36,78 +75,117 @@ impl ScopeState {
         self.scopes.get_mut(&id)
     }
 
-    pub fn create_environment(&mut self, parent_env: Option<EnvironmentId>) -> EnvironmentId {
+    /// Handle to an existing environment, e.g. for a function declared in it
+    pub fn get_handle(&self, id: EnvironmentId) -> Option<EnvironmentHandle> {
+        self.scopes.get(&id).map(|scope| scope.handle.clone())
+    }
+
+    /// The environment is collected once the returned handle and all of its copies are gone
+    pub fn create_environment(&mut self, parent_env: Option<EnvironmentId>) -> EnvironmentHandle {
+        if self.scopes.len() >= self.collection_threshold {
+            self.collect_garbage();
+            // collect again once the number of live environments doubles
+            self.collection_threshold = (self.scopes.len() * 2).max(MIN_COLLECTION_THRESHOLD);
+        }
         let env = Environment::new(parent_env);
-        let env_id = self.append_environment(env);
-        env_id
+        self.append_environment(env)
     }
 
-    pub fn append_environment(&mut self, mut environment: Environment) -> EnvironmentId {
-        if environment
-            .parent
-            .is_some_and(|parent_id| self.get_scope(parent_id).is_none())
-        {
-            panic!("Parent for provided scope is not found: {:?}", environment);
+    pub fn append_environment(&mut self, mut environment: Environment) -> EnvironmentHandle {
+        if let Some(parent_id) = environment.parent {
+            match self.get_handle(parent_id) {
+                // children keep their parent alive, see `release_environment`
+                Some(parent) => environment.parent_handle = Some(parent),
+                None => panic!("Parent for provided scope is not found: {:?}", environment),
+            }
         }
 
         let id = self.generate_scope_id();
 
         environment.id = id;
+        environment.handle = EnvironmentHandle(Arc::new(id));
+        let handle = environment.handle.clone();
 
         self.scopes.insert(id, environment);
 
-        id
+        handle
+    }
+
+    /// Removes the environment right away unless something captured it or one of its children,
+    /// so the scopes of blocks and calls don't pile up until the next collection
+    pub fn release_environment(&mut self, handle: EnvironmentHandle) {
+        let id = handle.id();
+        drop(handle);
+        if self
+            .get_scope(id)
+            .is_some_and(|scope| scope.handle.references() == 0)
+        {
+            self.scopes.remove(&id);
+        }
+    }
+
+    /// Top most environment of the chain, the one with the builtins
+    pub fn root_environment(&self, env_id: EnvironmentId) -> EnvironmentId {
+        match self.get_scope(env_id).and_then(|scope| scope.parent) {
+            Some(parent) => self.root_environment(parent),
+            None => env_id,
+        }
     }
 
     pub fn assign_variable(
         &mut self,
-        variable_name: String,
-        value: Arc<Mutex<Box<dyn RuntimeValue>>>,
+        variable_name: &str,
+        value: Value,
         env_id: EnvironmentId,
         ignore_constant: bool,
-    ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError> {
-        let env_id = self.resolve_mut(env_id, variable_name.clone())?;
-        let scope = self.get_scope_mut(env_id).unwrap();
-
-        if scope.constants.contains(&variable_name) && !ignore_constant {
-            bail!(InterpreterError::ReassignConstant(variable_name.clone()))
+    ) -> Result<Value, InterpreterError> {
+        let env_id = self.resolve(env_id, variable_name)?;
+        let scope = match self.get_scope_mut(env_id) {
+            Some(scope) => scope,
+            None => bail!(InterpreterError::UnresolvedEnvironment(env_id)),
+        };
+
+        if !ignore_constant && scope.constants.contains(variable_name) {
+            bail!(InterpreterError::ReassignConstant(
+                variable_name.to_string()
+            ))
         }
 
-        scope
-            .variables
-            .entry(variable_name.clone())
-            .and_modify(|val| *val = value);
-
-        let value = Arc::clone(scope.variables.get(&variable_name).unwrap());
+        match scope.variables.get_mut(variable_name) {
+            Some(variable) => *variable = value.clone(),
+            None => bail!(InterpreterError::UnresolvedVariable(
+                variable_name.to_string()
+            )),
+        }
         Ok(value)
     }
 
-    pub fn resolve_mut(
-        &mut self,
+    /// Environment that declares the variable, starting from `env_id` and going up the chain
+    pub fn resolve(
+        &self,
         env_id: EnvironmentId,
-        variable_name: String,
+        variable_name: &str,
     ) -> Result<EnvironmentId, InterpreterError> {
-        let scope = self.get_scope(env_id).unwrap();
-        if scope.variables.contains_key(&variable_name) {
-            return Ok(scope.id);
-        }
-
-        if scope.parent.is_none() {
-            bail!(InterpreterError::UnresolvedVariable(variable_name))
+        match self.get_scope(env_id) {
+            Some(scope) => scope.resolve(variable_name, self),
+            None => bail!(InterpreterError::UnresolvedEnvironment(env_id)),
         }
+    }
+}
 
-        // let scope_state = SCOPE_STATE.lock().unwrap();
-        let parent_scope = self.get_scope(scope.parent.unwrap()).unwrap();
-        let env_id = parent_scope.resolve(variable_name, self)?;
-        Ok(env_id)
+impl Default for ScopeState {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 pub struct Environment {
     pub id: EnvironmentId,
     pub parent: Option<EnvironmentId>,
-    pub variables: HashMap<String, Arc<Mutex<Box<dyn RuntimeValue>>>>,
-    pub constants: HashSet<String>,
+    pub variables: FastHashMap<String, Value>,
+    pub constants: FastHashSet<String>,
+    handle: EnvironmentHandle,
+    parent_handle: Option<EnvironmentHandle>,
 }
 
 impl Debug for Environment {
@@ -128,118 +206,88 @@ impl Environment {
         Self {
             id: 0,
             parent: parent_env,
-            variables: HashMap::new(),
-            constants: HashSet::new(),
+            variables: FastHashMap::default(),
+            constants: FastHashSet::default(),
+            handle: EnvironmentHandle::default(),
+            parent_handle: None,
         }
     }
 
     pub fn declare_variable(
         &mut self,
         variable_name: String,
-        value: Arc<Mutex<Box<dyn RuntimeValue>>>,
+        value: Value,
         is_constant: bool,
-    ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError> {
-        if self.variables.contains_key(&variable_name) {
-            bail!(InterpreterError::VariableDeclarationExist(variable_name))
-        }
-
-        self.variables.insert(variable_name.clone(), value);
+    ) -> Result<Value, InterpreterError> {
+        let entry = match self.variables.entry(variable_name) {
+            Entry::Occupied(entry) => {
+                bail!(InterpreterError::VariableDeclarationExist(
+                    entry.key().clone()
+                ))
+            }
+            Entry::Vacant(entry) => entry,
+        };
 
         if is_constant {
-            self.constants.insert(variable_name.clone());
+            self.constants.insert(entry.key().clone());
         }
 
-        Ok(Arc::clone(self.variables.get(&variable_name).unwrap()))
+        Ok(entry.insert(value).clone())
     }
 
-    // pub fn assign_variable(
-    //     &mut self,
-    //     variable_name: String,
-    //     value: Arc<Mutex<Box<dyn RuntimeValue>>>,
-    // ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError> {
-    //     let env_id = self.resolve_mut(variable_name.clone())?;
-    //     let mut scope_state = SCOPE_STATE.lock().unwrap();
-    //     let scope = scope_state.get_scope_mut(env_id).unwrap();
-
-    //     if scope.constants.contains(&variable_name) {
-    //         bail!(InterpreterError::ReassignConstant(variable_name.clone()))
-    //     }
-
-    //     scope
-    //         .variables
-    //         .entry(variable_name.clone())
-    //         .and_modify(|val| *val = value);
-
-    //     let value = Arc::clone(scope.variables.get(&variable_name).unwrap());
-    //     drop(scope_state);
-    //     Ok(value)
-    // }
-
     pub fn lookup_variable(
         &self,
-        variable_name: String,
+        variable_name: &str,
         scope_state: &ScopeState,
-    ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError> {
-        let env_id = self.resolve(variable_name.clone(), scope_state)?;
-        let env = scope_state.get_scope(env_id).unwrap();
-        let value = Arc::clone(env.variables.get(&variable_name).unwrap());
-        Ok(value)
+    ) -> Result<Value, InterpreterError> {
+        match self.find_variable(variable_name, scope_state) {
+            Some(value) => Ok(value.clone()),
+            None => bail!(InterpreterError::UnresolvedVariable(
+                variable_name.to_string()
+            )),
+        }
     }
 
     pub fn lookup_variable_safe(
         &self,
-        variable_name: String,
+        variable_name: &str,
         scope_state: &ScopeState,
-    ) -> Option<Arc<Mutex<Box<dyn RuntimeValue>>>> {
-        let env_id = self.resolve(variable_name.clone(), scope_state);
-        if env_id.is_err() {
-            return None;
-        }
-        let env = scope_state.get_scope(env_id.unwrap()).unwrap();
-        if let Some(variable) = env.variables.get(&variable_name) {
-            let value = variable
-                .lock()
-                .expect("lookup_variable_safe: failed to get variable value");
-            return Some(Arc::new(Mutex::new(dyn_clone::clone_box(&**value))));
-        }
-        return None;
+    ) -> Option<Value> {
+        self.find_variable(variable_name, scope_state).cloned()
     }
 
-    // pub fn resolve_mut(
-    //     &mut self,
-    //     variable_name: String,
-    // ) -> Result<EnvironmentId, InterpreterError> {
-    //     if self.variables.contains_key(&variable_name) {
-    //         return Ok(self.id);
-    //     }
-
-    //     if self.parent.is_none() {
-    //         bail!(InterpreterError::UnresolvedVariable(variable_name))
-    //     }
-
-    //     let mut scope_state = SCOPE_STATE.lock().unwrap();
-    //     let parent_scope = scope_state.get_scope_mut(self.parent.unwrap()).unwrap();
-    //     let env_id = parent_scope.resolve_mut(variable_name)?;
-    //     drop(scope_state);
-    //     Ok(env_id)
-    // }
+    /// Value of the variable in the closest environment of the chain that declares it
+    fn find_variable<'a>(
+        &'a self,
+        variable_name: &str,
+        scope_state: &'a ScopeState,
+    ) -> Option<&'a Value> {
+        let mut scope = self;
+        loop {
+            if let Some(value) = scope.variables.get(variable_name) {
+                return Some(value);
+            }
+            scope = scope_state.get_scope(scope.parent?)?;
+        }
+    }
 
     pub fn resolve(
         &self,
-        variable_name: String,
+        variable_name: &str,
         scope_state: &ScopeState,
     ) -> Result<EnvironmentId, InterpreterError> {
-        if self.variables.contains_key(&variable_name) {
-            return Ok(self.id);
+        let mut scope = self;
+        while !scope.variables.contains_key(variable_name) {
+            scope = match scope
+                .parent
+                .and_then(|parent| scope_state.get_scope(parent))
+            {
+                Some(parent_scope) => parent_scope,
+                None => bail!(InterpreterError::UnresolvedVariable(
+                    variable_name.to_string()
+                )),
+            };
         }
-
-        if self.parent.is_none() {
-            bail!(InterpreterError::UnresolvedVariable(variable_name))
-        }
-
-        // let scope_state = SCOPE_STATE.lock().unwrap();
-        let parent_scope = scope_state.get_scope(self.parent.unwrap()).unwrap();
-        let env_id = parent_scope.resolve(variable_name, scope_state)?;
-        Ok(env_id)
+        Ok(scope.id)
     }
 }
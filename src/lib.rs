@@ -3,8 +3,10 @@ mod environment;
 mod macros;
 mod runtime;
 mod values;
+mod vm;
 
 pub use builtin::*;
 pub use environment::*;
 pub use runtime::*;
 pub use values::*;
+pub use vm::*;
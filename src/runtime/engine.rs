@@ -0,0 +1,225 @@
+use std::{collections::HashMap, fs, sync::Arc};
+
+use pl_ast::{BinaryOperator, Node};
+
+use crate::{
+    macros::bail, ClassInstanceValue, ClassValue, EnvironmentHandle, EnvironmentId, Key,
+    NativeContext, ScopeState, Value,
+};
+
+use super::{
+    error::InterpreterError,
+    module::{parse_source, resolve_module_path, Module, Modules},
+    operators::{self, operator_method_name},
+};
+
+/// What the tree walker and the bytecode VM have in common, they only differ in how
+/// they call methods and run code, everything built on top of that is shared
+pub trait Engine {
+    fn scope_state_mut(&mut self) -> &mut ScopeState;
+
+    fn modules_mut(&mut self) -> &mut Modules;
+
+    /// Calls the method of the class with `receiver` as `self`,
+    /// `None` if neither the class nor its super classes define it
+    fn call_class_method(
+        &mut self,
+        class: &Arc<ClassValue>,
+        receiver: Value,
+        name: &str,
+        args: Vec<Value>,
+    ) -> Result<Option<Value>, InterpreterError>;
+
+    /// Runs the top level of a module in its own environment
+    fn run_module(&mut self, ast: Node, env: EnvironmentHandle) -> Result<(), InterpreterError>;
+
+    /// Calls a dunder method of a class instance, `None` if the value doesn't define it
+    fn eval_dunder_method(
+        &mut self,
+        value: &Value,
+        method_name: &str,
+        args: Vec<Value>,
+    ) -> Result<Option<Value>, InterpreterError> {
+        match value {
+            Value::ClassInstance(instance) => {
+                let class = instance.class().clone();
+                self.call_class_method(&class, value.clone(), method_name, args)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Binary operation with operator overloading,
+    /// e.g. `a + b` calls `a.__add__(b)` and `a != b` is `!a.__eq__(b)`
+    fn eval_binary_values(
+        &mut self,
+        left: Value,
+        right: Value,
+        operator: BinaryOperator,
+    ) -> Result<Value, InterpreterError> {
+        if let Value::ClassInstance(_) = left {
+            let method_name = operator_method_name(&operator);
+            if let Some(value) = self.eval_dunder_method(&left, method_name, vec![right.clone()])? {
+                if operator != BinaryOperator::NotEquals {
+                    return Ok(value);
+                }
+                return match value {
+                    Value::Boolean(is_equal) => Ok(Value::from(!is_equal)),
+                    value => bail!(InterpreterError::InvalidValue(value, "boolean".to_string())),
+                };
+            }
+        }
+        operators::eval_binary_values(left, right, operator)
+    }
+
+    /// `-value`, `error` is raised for values that aren't numbers and don't define `__neg__`
+    fn negate(
+        &mut self,
+        value: Value,
+        error: impl FnOnce() -> InterpreterError,
+    ) -> Result<Value, InterpreterError> {
+        if let Some(value) = self.eval_dunder_method(&value, "__neg__", vec![])? {
+            return Ok(value);
+        }
+        match value {
+            Value::Decimal(value) => Ok(Value::from(-value)),
+            // -isize::MIN doesn't fit into isize
+            Value::Integer(value) => match value.checked_neg() {
+                Some(negated) => Ok(Value::from(negated)),
+                None => bail!(InterpreterError::ArithmeticError(
+                    BinaryOperator::Minus,
+                    Value::Integer(0),
+                    Value::Integer(value)
+                )),
+            },
+            _ => bail!(error()),
+        }
+    }
+
+    /// New instance of the class, the constructor gets the arguments
+    fn instantiate(
+        &mut self,
+        class: Arc<ClassValue>,
+        args: Vec<Value>,
+    ) -> Result<Value, InterpreterError> {
+        let mut instance_map: HashMap<Key, Value> = HashMap::new();
+        for property in class.properties.iter().filter(|prop| !prop.is_static) {
+            // every instance gets its own copy of arrays and objects from the class body
+            instance_map.insert(property.name.clone(), property.value.deep_copy());
+        }
+        // methods aren't copied, they are looked up through the class of the instance
+        let instance = Value::from(ClassInstanceValue::from((class.clone(), instance_map)));
+
+        // constructors are inherited like any other method
+        let arg_count = args.len();
+        let constructed = self.call_class_method(&class, instance.clone(), "__new__", args)?;
+        if constructed.is_none() && arg_count > 0 {
+            bail!(InterpreterError::InvalidParameterCount(0, arg_count))
+        }
+        Ok(instance)
+    }
+
+    /// Calls a method of the super class of `class` with `receiver` as `self`,
+    /// `super(...)` calls the constructor
+    fn call_super(
+        &mut self,
+        class: &ClassValue,
+        receiver: Value,
+        method_name: &str,
+        args: Vec<Value>,
+    ) -> Result<Value, InterpreterError> {
+        let super_class = match &class.super_class {
+            Some(super_class) => super_class.clone(),
+            None => bail!(InterpreterError::InvalidSuper),
+        };
+
+        let arg_count = args.len();
+        match self.call_class_method(&super_class, receiver, method_name, args)? {
+            Some(value) => Ok(value),
+            // classes without a constructor don't take any arguments
+            None if method_name == "__new__" => {
+                if arg_count > 0 {
+                    bail!(InterpreterError::InvalidParameterCount(0, arg_count))
+                }
+                Ok(Value::Null)
+            }
+            None => bail!(InterpreterError::UnresolvedProperty(
+                method_name.to_string()
+            )),
+        }
+    }
+
+    /// Evaluates the module once, later imports get it from the cache
+    fn load_module(&mut self, path: &str, env: EnvironmentId) -> Result<Module, InterpreterError> {
+        let modules = self.modules_mut();
+        let module_path = resolve_module_path(path, modules.stack.last(), &modules.search_paths)?;
+        if let Some(module) = modules.loaded.get(&module_path) {
+            return Ok(module.clone());
+        }
+        if modules
+            .stack
+            .iter()
+            .any(|module| module.path == module_path)
+        {
+            bail!(InterpreterError::CyclicImport(path.to_string()))
+        }
+
+        let source = match fs::read_to_string(&module_path) {
+            Ok(source) => source,
+            Err(_) => bail!(InterpreterError::ModuleNotFound(path.to_string())),
+        };
+        let ast = match parse_source(source) {
+            Ok(ast) => ast,
+            Err(err) => bail!(InterpreterError::ModuleParseError(path.to_string(), err)),
+        };
+
+        // modules only share the builtins, not the scope of the importing file
+        let scope_state = self.scope_state_mut();
+        let root_env = scope_state.root_environment(env);
+        let module_env = scope_state.create_environment(Some(root_env));
+
+        self.modules_mut()
+            .stack
+            .push(Module::new(module_path.clone(), module_env.clone()));
+        let result = self.run_module(ast, module_env);
+        let module = self.modules_mut().stack.pop().unwrap();
+        result?;
+
+        self.modules_mut()
+            .loaded
+            .insert(module_path, module.clone());
+        Ok(module)
+    }
+}
+
+impl<T: Engine> NativeContext for T {
+    fn call_str_method(&mut self, instance: &Value) -> Result<Option<String>, InterpreterError> {
+        match self.eval_dunder_method(instance, "__str__", vec![])? {
+            Some(Value::String(display)) => Ok(Some(display.to_string())),
+            Some(display) => bail!(InterpreterError::InvalidValue(
+                display,
+                "string".to_string()
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Fails unless there are arguments for all of the required parameters,
+/// extra arguments are only allowed when there is a rest parameter to collect them
+pub fn check_argument_count(
+    required: usize,
+    parameters: usize,
+    is_variadic: bool,
+    arg_count: usize,
+) -> Result<(), InterpreterError> {
+    if arg_count < required {
+        bail!(InterpreterError::InvalidParameterCount(required, arg_count))
+    }
+    if !is_variadic && arg_count > parameters {
+        bail!(InterpreterError::InvalidParameterCount(
+            parameters, arg_count
+        ))
+    }
+    Ok(())
+}
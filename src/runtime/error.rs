@@ -1,30 +1,117 @@
-use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
 
 use pl_ast::{BinaryOperator, Node, UnaryOperator};
 
-use crate::{stringify, values::RuntimeValue, EnvironmentId};
+use crate::{
+    stringify,
+    values::{Value, ValueType},
+    EnvironmentId, Key, ObjectValue, MAX_RANGE_LENGTH,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum InterpreterError {
     UnsupportedNode(Box<Node>),
     UnsupportedBinaryOperator(BinaryOperator),
     UnsupportedUnaryOperator(UnaryOperator),
-    UnsupportedValue(Arc<Mutex<Box<dyn RuntimeValue>>>),
+    UnsupportedOperands(BinaryOperator, ValueType, ValueType),
+    UnsupportedValue(Value),
     UnexpectedNode(Box<Node>),
-    UnexpectedValue(Box<dyn RuntimeValue>),
-    ValueCastError(Box<dyn RuntimeValue>, String),
+    UnexpectedValue(Value),
+    ValueCastError(Value, String),
     VariableDeclarationExist(String),
     UnresolvedVariable(String),
     UnresolvedProperty(String),
     ReassignConstant(String),
     InvalidAssignFactor(Box<Node>),
-    InvalidFunctionCallee(Arc<Mutex<Box<dyn RuntimeValue>>>),
+    InvalidFunctionCallee(Value),
     InvalidFunctionParameter(Box<Node>),
-    InvalidCondition(Box<dyn RuntimeValue>),
-    InvalidValue(Box<dyn RuntimeValue>, String),
+    InvalidCondition(Value),
+    InvalidValue(Value, String),
     InvalidDefaultParameter(String),
     InvalidParameterCount(usize, usize),
     UnresolvedEnvironment(EnvironmentId),
+    Return(Value),
+    Break,
+    Continue,
+    InvalidLoopControl(String),
+    InvalidSuper,
+    /// Receiver of a method and the value assigned to `self` that can't replace it
+    InvalidSelfAssignment(Value, Value),
+    IndexOutOfRange(isize, usize),
+    /// Start and end of a range with more than `MAX_RANGE_LENGTH` items
+    RangeTooLarge(isize, isize),
+    ModuleNotFound(String),
+    ModuleParseError(String, String),
+    CyclicImport(String),
+    UnresolvedExport(String, String),
+    /// Integer overflow, division or modulo by zero, or a non-finite decimal result.
+    /// Carries the operator together with its left and right operands.
+    ArithmeticError(BinaryOperator, Value, Value),
+    Throw(Value),
+}
+
+impl InterpreterError {
+    /// Control flow signals unwind through try statements without being caught
+    pub fn is_catchable(&self) -> bool {
+        !matches!(
+            self,
+            InterpreterError::Return(_) | InterpreterError::Break | InterpreterError::Continue
+        )
+    }
+
+    /// Name of the error that scripts see as the `kind` of a caught error
+    pub fn kind(&self) -> String {
+        let kind = match self {
+            InterpreterError::UnsupportedNode(_) => "UnsupportedNode",
+            InterpreterError::UnsupportedBinaryOperator(_) => "UnsupportedBinaryOperator",
+            InterpreterError::UnsupportedUnaryOperator(_) => "UnsupportedUnaryOperator",
+            InterpreterError::UnsupportedOperands(..) => "UnsupportedOperands",
+            InterpreterError::UnsupportedValue(_) => "UnsupportedValue",
+            InterpreterError::UnexpectedNode(_) => "UnexpectedNode",
+            InterpreterError::UnexpectedValue(_) => "UnexpectedValue",
+            InterpreterError::ValueCastError(..) => "ValueCastError",
+            InterpreterError::VariableDeclarationExist(_) => "VariableDeclarationExist",
+            InterpreterError::UnresolvedVariable(_) => "UnresolvedVariable",
+            InterpreterError::UnresolvedProperty(_) => "UnresolvedProperty",
+            InterpreterError::ReassignConstant(_) => "ReassignConstant",
+            InterpreterError::InvalidAssignFactor(_) => "InvalidAssignFactor",
+            InterpreterError::InvalidFunctionCallee(_) => "InvalidFunctionCallee",
+            InterpreterError::InvalidFunctionParameter(_) => "InvalidFunctionParameter",
+            InterpreterError::InvalidCondition(_) => "InvalidCondition",
+            InterpreterError::InvalidValue(..) => "InvalidValue",
+            InterpreterError::InvalidDefaultParameter(_) => "InvalidDefaultParameter",
+            InterpreterError::InvalidParameterCount(..) => "InvalidParameterCount",
+            InterpreterError::UnresolvedEnvironment(_) => "UnresolvedEnvironment",
+            InterpreterError::Return(_) => "Return",
+            InterpreterError::Break => "Break",
+            InterpreterError::Continue => "Continue",
+            InterpreterError::InvalidLoopControl(_) => "InvalidLoopControl",
+            InterpreterError::InvalidSuper => "InvalidSuper",
+            InterpreterError::InvalidSelfAssignment(..) => "InvalidSelfAssignment",
+            InterpreterError::IndexOutOfRange(..) => "IndexOutOfRange",
+            InterpreterError::RangeTooLarge(..) => "RangeTooLarge",
+            InterpreterError::ModuleNotFound(_) => "ModuleNotFound",
+            InterpreterError::ModuleParseError(..) => "ModuleParseError",
+            InterpreterError::CyclicImport(_) => "CyclicImport",
+            InterpreterError::UnresolvedExport(..) => "UnresolvedExport",
+            InterpreterError::ArithmeticError(..) => "ArithmeticError",
+            InterpreterError::Throw(_) => "Throw",
+        };
+        kind.to_string()
+    }
+
+    /// Value a catch clause gets, thrown values are caught as they are
+    /// and internal errors become `{ kind, message }` objects
+    pub fn into_value(self) -> Value {
+        if let InterpreterError::Throw(value) = self {
+            return value;
+        }
+
+        let mut map: HashMap<Key, Value> = HashMap::new();
+        map.insert("kind".to_string(), Value::from(self.kind()));
+        map.insert("message".to_string(), Value::from(self.to_string()));
+        Value::from(ObjectValue::from(map))
+    }
 }
 
 impl std::fmt::Display for InterpreterError {
@@ -57,23 +144,18 @@ impl std::fmt::Display for InterpreterError {
                     operator
                 )
             }
-            InterpreterError::UnsupportedValue(value) => {
+            InterpreterError::UnsupportedOperands(operator, left, right) => {
                 write!(
                     f,
-                    "Unsupported value: {}",
-                    stringify(dyn_clone::clone_box(
-                        &**value
-                            .lock()
-                            .expect("error: failed to get unsupported value")
-                    ))
+                    "Operator {:?} is not supported between {:?} and {:?}",
+                    operator, left, right
                 )
             }
+            InterpreterError::UnsupportedValue(value) => {
+                write!(f, "Unsupported value: {}", stringify(value))
+            }
             InterpreterError::UnexpectedValue(value) => {
-                write!(
-                    f,
-                    "Unexpected value: {}",
-                    stringify(dyn_clone::clone_box(&**value))
-                )
+                write!(f, "Unexpected value: {}", stringify(value))
             }
             InterpreterError::VariableDeclarationExist(variable_name) => {
                 write!(
@@ -95,31 +177,19 @@ impl std::fmt::Display for InterpreterError {
                 )
             }
             InterpreterError::InvalidFunctionCallee(value) => {
-                write!(
-                    f,
-                    "Invalid function callee: {:?}",
-                    stringify(dyn_clone::clone_box(
-                        &**(value
-                            .lock()
-                            .expect("error: failed to get function callee value"))
-                    ))
-                )
+                write!(f, "Invalid function callee: {:?}", stringify(value))
             }
             InterpreterError::InvalidFunctionParameter(parameter) => {
                 write!(f, "Invalid parameter provided in function: {parameter:?}")
             }
             InterpreterError::InvalidCondition(condition) => {
-                write!(
-                    f,
-                    "Invalid condition: {:?}",
-                    stringify(dyn_clone::clone_box(&**condition))
-                )
+                write!(f, "Invalid condition: {:?}", stringify(condition))
             }
             InterpreterError::InvalidValue(value, expected) => {
                 write!(
                     f,
                     "Invalid value: {}, expected {}",
-                    stringify(dyn_clone::clone_box(&**value)),
+                    stringify(value),
                     expected
                 )
             }
@@ -143,6 +213,58 @@ impl std::fmt::Display for InterpreterError {
             Self::UnresolvedEnvironment(env) => {
                 write!(f, "Unresolved environment: {}", env)
             }
+            Self::Return(_) => {
+                write!(f, "Unexpected return statement outside of function")
+            }
+            Self::Break => {
+                write!(f, "Cannot use \"break\" outside of a loop")
+            }
+            Self::Continue => {
+                write!(f, "Cannot use \"continue\" outside of a loop")
+            }
+            Self::ArithmeticError(operator, left, right) => {
+                write!(
+                    f,
+                    "Arithmetic error: {} {:?} {} overflows or divides by zero",
+                    stringify(left),
+                    operator,
+                    stringify(right)
+                )
+            }
+            Self::Throw(value) => {
+                write!(f, "Uncaught exception: {}", stringify(value))
+            }
+            Self::InvalidLoopControl(statement) => {
+                write!(f, "Cannot use \"{statement}\" outside of a loop")
+            }
+            Self::InvalidSuper => {
+                write!(f, "Cannot use \"super\" outside of a method of a subclass")
+            }
+            Self::InvalidSelfAssignment(receiver, value) => {
+                write!(
+                    f,
+                    "Cannot assign {} to self of {}, self can only be replaced by a value of its own type",
+                    stringify(value),
+                    stringify(receiver)
+                )
+            }
+            Self::IndexOutOfRange(index, length) => {
+                write!(f, "Index {index} is out of range for length {length}")
+            }
+            Self::RangeTooLarge(start, end) => {
+                write!(
+                    f,
+                    "Range from {start} to {end} has more than {MAX_RANGE_LENGTH} items"
+                )
+            }
+            Self::ModuleNotFound(path) => write!(f, "Cannot find module \"{path}\""),
+            Self::ModuleParseError(path, error) => {
+                write!(f, "Failed to parse module \"{path}\": {error}")
+            }
+            Self::CyclicImport(path) => write!(f, "Cyclic import of module \"{path}\""),
+            Self::UnresolvedExport(path, name) => {
+                write!(f, "Module \"{path}\" doesn't export \"{name}\"")
+            }
         }
     }
 }
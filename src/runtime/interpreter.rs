@@ -1,139 +1,136 @@
 use std::{
     collections::{HashMap, VecDeque},
-    fmt::Debug,
-    sync::{Arc, Mutex},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
 };
 
-use lazy_static::lazy_static;
-use pl_ast::{
-    token::{Token, TokenKind},
-    AssignmentOperator, BinaryOperator, Lexer, LogicalOperator, Node, Parser, UnaryOperator,
-};
+use pl_ast::{AssignmentOperator, LogicalOperator, Node, UnaryOperator};
 
 use crate::{
-    cast_value, get_array_object, get_number_object, get_string_object,
-    macros::bail,
-    stringify,
-    values::{DecimalValue, IntegerValue, NullValue, RuntimeValue, ValueType},
-    ArrayValue, BoolValue, ClassMethod, ClassMethodParameter, ClassProperty, ClassValue,
-    EnvironmentId, FunctionParameter, FunctionValue, Key, NativeFnValue, ObjectValue, ScopeState,
-    StringValue, Value,
+    macros::bail, setup_default_environment, ArrayValue, ClassInstanceValue, ClassMethod,
+    ClassMethodParameter, ClassProperty, ClassValue, EnvironmentHandle, EnvironmentId,
+    FunctionParameter, FunctionValue, Key, ObjectValue, Prototypes, ScopeState, Value,
 };
 
-use super::error::InterpreterError;
-
-lazy_static! {
-    pub static ref SCOPE_STATE: Arc<Mutex<ScopeState>> = Arc::new(Mutex::new(ScopeState::new()));
-}
+use super::{
+    engine::{check_argument_count, Engine},
+    error::InterpreterError,
+    module::{parse_source, Module, Modules},
+    operators::{get_binary_operator, get_element, get_items, resolve_index},
+    resolver::Resolver,
+};
 
 pub struct Interpreter {
     stack: VecDeque<(Box<Node>, EnvironmentId)>,
+    /// Environments of this interpreter, other interpreters never see them
+    scope_state: ScopeState,
+    /// Environment with the builtins, the main script runs in it
+    global_env: EnvironmentHandle,
+    modules: Modules,
+    /// Methods of the builtin values, including the ones added by scripts
+    prototypes: Prototypes,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
+        let mut scope_state = ScopeState::new();
+        let global_env = setup_default_environment(&mut scope_state);
         Self {
             stack: VecDeque::new(),
+            scope_state,
+            global_env,
+            modules: Modules::default(),
+            prototypes: Prototypes::new(),
         }
     }
 
+    pub fn with_search_paths(mut self, search_paths: Vec<PathBuf>) -> Self {
+        self.modules.search_paths = search_paths;
+        self
+    }
+
+    pub fn global_env(&self) -> EnvironmentId {
+        self.global_env.id()
+    }
+
+    pub fn scope_state(&self) -> &ScopeState {
+        &self.scope_state
+    }
+
     // TODO: change error type to custom error instead of String
-    pub fn run_from_source(source: String, env_id: EnvironmentId) -> Result<(), String> {
-        let mut lexer = Lexer::new(source);
-        lexer.tokenize().map_err(|err| format!("{}", err))?;
-        let mut parser = Parser::new(
-            lexer
-                .tokens
-                .iter()
-                .filter(|token| token.kind() != TokenKind::Newline)
-                .map(|token| dyn_clone::clone_box(&**token))
-                .collect::<Vec<Box<dyn Token>>>(),
-        );
-        let ast = parser.produce_ast().map_err(|err| format!("{}", err))?;
-        let mut interpreter = Interpreter::new();
-        interpreter
-            .run(Box::new(ast), env_id)
+    pub fn run_from_source(&mut self, source: String) -> Result<(), String> {
+        let ast = parse_source(source)?;
+        self.run(Box::new(ast), self.global_env.id())
             .map_err(|err| format!("{}", err))
     }
 
+    /// Runs the file as the main module, so its imports are resolved relative to it
+    pub fn run_file(&mut self, path: &Path) -> Result<(), String> {
+        let source = fs::read_to_string(path).map_err(|err| format!("{}", err))?;
+        let ast = parse_source(source)?;
+        let path = path.canonicalize().map_err(|err| format!("{}", err))?;
+        self.modules
+            .stack
+            .push(Module::new(path, self.global_env.clone()));
+        let result = self.run(Box::new(ast), self.global_env.id());
+        self.modules.stack.pop();
+        result.map_err(|err| format!("{}", err))
+    }
+
     pub fn run(&mut self, node: Box<Node>, env: EnvironmentId) -> Result<(), InterpreterError> {
+        // unresolved names are reported before anything runs
+        Resolver::new(&self.scope_state, env).check_program(&node)?;
         match *node {
             Node::Program(statements) | Node::BlockStatement(statements) => {
                 for statement in statements {
                     self.stack.push_back((statement, env));
                 }
             }
-            Node::IfStatement(condition, body, alternate) => {
-                self.eval_if_statement(condition, body, alternate, env)?;
-            }
-            Node::WhileStatement(condition, consequent) => {
-                self.eval_while_statement(condition, consequent, env)?;
-            }
-            Node::CallExpression(calle, args) => {
-                self.eval_call_expression(calle, args, env)?;
-            }
-            Node::ClassDeclaration(id, super_class, body) => {
-                self.eval_class_declaration(id, super_class, body, env)?;
-            }
-            // TODO: Add support for ForInStatement
-            node => {
-                self.resolve(Box::new(node), env)?;
+            _ => {
+                self.resolve(&node, env)?;
             }
         }
 
-        self.execute()?;
-
-        Ok(())
+        self.execute()
     }
 
     pub fn execute(&mut self) -> Result<(), InterpreterError> {
         while let Some((current_node, current_env)) = self.stack.pop_front() {
-            match *current_node {
-                Node::CallExpression(calle, args) => {
-                    self.eval_call_expression(calle, args, current_env)?;
-                }
-                Node::IfStatement(condition, body, alternate) => {
-                    self.eval_if_statement(condition, body, alternate, current_env)?;
-                }
-                Node::WhileStatement(condition, consequent) => {
-                    self.eval_while_statement(condition, consequent, current_env)?;
-                }
-                Node::ClassDeclaration(id, super_class, body) => {
-                    self.eval_class_declaration(id, super_class, body, current_env)?;
-                }
-                node => {
-                    self.resolve(Box::new(node), current_env)?;
-                }
-            }
+            self.resolve(&current_node, current_env)?;
         }
 
         Ok(())
     }
 
-    pub fn resolve(
-        &mut self,
-        node: Box<Node>,
-        env: EnvironmentId,
-    ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError> {
-        let value: Arc<Mutex<Box<dyn RuntimeValue>>> = match *node {
-            Node::IntegerLiteral(value) => {
-                Arc::new(Mutex::new(Box::new(IntegerValue::from(value as isize))))
-            }
-            Node::DecimalLiteral(value) => {
-                Arc::new(Mutex::new(Box::new(DecimalValue::from(value))))
-            }
-            Node::StringLiteral(value) => Arc::new(Mutex::new(Box::new(StringValue::from(value)))),
+    pub fn resolve(&mut self, node: &Node, env: EnvironmentId) -> Result<Value, InterpreterError> {
+        let value = match node {
+            Node::IntegerLiteral(value) => Value::from(*value as isize),
+            Node::DecimalLiteral(value) => Value::from(*value),
+            Node::StringLiteral(value) => Value::from(value.clone()),
             Node::ArrayExpression(items) => {
-                let mut values: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>> = vec![];
+                let mut values: Vec<Value> = vec![];
                 for item in items {
                     values.push(self.resolve(item, env)?);
                 }
-                Arc::new(Mutex::new(Box::new(ArrayValue::from(values))))
+                Value::from(ArrayValue::from(values))
+            }
+            Node::ObjectExpression(properties) => {
+                let mut map: HashMap<Key, Value> = HashMap::new();
+                for property in properties {
+                    match &**property {
+                        Node::Property(key, value) => {
+                            map.insert(key.clone(), self.resolve(value, env)?);
+                        }
+                        node => bail!(InterpreterError::UnexpectedNode(Box::new(node.clone()))),
+                    }
+                }
+                Value::from(ObjectValue::from(map))
             }
             Node::BinaryExpression(..) => self.eval_binary_expression(node, env)?,
             Node::Identifier(identifier) => self.eval_identifier(identifier, env)?,
             Node::VariableDeclaration(variable_name, value, is_constant) => {
-                self.eval_variable_declaration(variable_name, value, is_constant, env)?
+                self.eval_variable_declaration(variable_name, value.as_deref(), *is_constant, env)?
             }
             Node::AssignmentExpression(left, operator, right) => {
                 self.eval_assignment_expression(left, operator, right, env)?
@@ -141,8 +138,11 @@ impl Interpreter {
             Node::FunctionDeclaration(name, parameters, body) => {
                 self.eval_function_declaration(name, parameters, body, env)?
             }
+            Node::FunctionExpression(parameters, body) => {
+                self.eval_function_expression(parameters, body, env)?
+            }
             Node::MemberExpression(object, property, computed) => {
-                self.eval_member_expression(object, property, computed, env)?
+                self.eval_member_expression(object, property, *computed, env)?
             }
             Node::UnaryExpression(expression, operator) => {
                 self.eval_unary_expression(expression, operator, env)?
@@ -150,320 +150,484 @@ impl Interpreter {
             Node::LogicalExpression(left, operator, right) => {
                 self.eval_logical_expression(left, operator, right, env)?
             }
-            Node::BlockStatement(statements) => {
-                let mut stack: VecDeque<Box<Node>> = VecDeque::new();
-                for statement in statements {
-                    stack.push_back(statement);
-                }
-                let mut result: Arc<Mutex<Box<dyn RuntimeValue>>> =
-                    Arc::new(Mutex::new(Box::new(NullValue::default())));
-                while let Some(statement) = stack.pop_front() {
-                    if let Node::ReturnStatement(value) = *statement {
-                        result = self.resolve(value, env)?;
-                        break;
-                    } else {
-                        self.resolve(statement, env)?;
-                    }
-                }
-                result
-            }
+            Node::BlockStatement(statements) => self.eval_block_statement(statements, env)?,
             Node::IfStatement(condition, body, alternate) => {
-                self.eval_if_statement(condition, body, alternate, env)?
+                self.eval_if_statement(condition, body, alternate.as_deref(), env)?
             }
             Node::WhileStatement(condition, consequent) => {
                 self.eval_while_statement(condition, consequent, env)?
             }
+            Node::ForInStatement(left, iterable, body) => {
+                self.eval_for_in_statement(left, iterable, body, env)?
+            }
             Node::CallExpression(calle, args) => self.eval_call_expression(calle, args, env)?,
-            Node::ReturnStatement(..) => bail!(InterpreterError::UnexpectedNode(node)),
-            node => bail!(InterpreterError::UnsupportedNode(Box::new(node))),
+            Node::ClassDeclaration(name, super_class, body) => {
+                self.eval_class_declaration(name, super_class.as_deref(), body, env)?
+            }
+            Node::ImportDeclaration(names, path) => {
+                self.eval_import_declaration(names, path, env)?
+            }
+            Node::ExportDeclaration(declaration) => {
+                self.eval_export_declaration(declaration, env)?
+            }
+            Node::TryStatement(block, handler, finalizer) => {
+                self.eval_try_statement(block, handler.as_deref(), finalizer.as_deref(), env)?
+            }
+            Node::ThrowStatement(value) => {
+                let value = self.resolve(value, env)?;
+                bail!(InterpreterError::Throw(value))
+            }
+            // both unwind up to the closest loop, see `eval_while_statement`
+            Node::BreakStatement => bail!(InterpreterError::Break),
+            Node::ContinueStatement => bail!(InterpreterError::Continue),
+            Node::ReturnStatement(value) => {
+                // unwinds up to the closest function call, see `eval_function_body`
+                let value = self.resolve(value, env)?;
+                bail!(InterpreterError::Return(value))
+            }
+            node => bail!(InterpreterError::UnsupportedNode(Box::new(node.clone()))),
         };
 
-        let v = value.lock().expect("resolve: failed to clone value");
-        let res = Arc::new(Mutex::new(dyn_clone::clone_box(&**v)));
+        Ok(value)
+    }
+
+    fn eval_block_statement(
+        &mut self,
+        statements: &[Box<Node>],
+        env_id: EnvironmentId,
+    ) -> Result<Value, InterpreterError> {
+        // declarations inside of the block must not leak into the parent scope,
+        // blocks without any can run in the parent scope, e.g. most loop bodies
+        let has_declarations = statements.iter().any(|statement| {
+            matches!(
+                **statement,
+                Node::VariableDeclaration(..)
+                    | Node::FunctionDeclaration(..)
+                    | Node::ClassDeclaration(..)
+            )
+        });
+        if !has_declarations {
+            return self.eval_statements(statements, env_id);
+        }
 
-        Ok(res)
+        let block_env = self.scope_state.create_environment(Some(env_id));
+        let result = self.eval_statements(statements, block_env.id());
+        self.scope_state.release_environment(block_env);
+        result
+    }
+
+    fn eval_statements(
+        &mut self,
+        statements: &[Box<Node>],
+        env_id: EnvironmentId,
+    ) -> Result<Value, InterpreterError> {
+        for statement in statements {
+            self.resolve(statement, env_id)?;
+        }
+        Ok(Value::Null)
     }
 
     fn eval_while_statement(
         &mut self,
-        condition: Box<Node>,
-        body: Box<Node>,
+        condition: &Node,
+        body: &Node,
         env_id: EnvironmentId,
-    ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError> {
-        let condition_resolved = self.resolve(condition.clone(), env_id)?;
-        let condition_innner = condition_resolved
-            .lock()
-            .expect("while_statement: failed to get condition");
-        if condition_innner.kind() != ValueType::Boolean {
-            bail!(InterpreterError::InvalidCondition(dyn_clone::clone_box(
-                &**condition_innner
-            )))
+    ) -> Result<Value, InterpreterError> {
+        while self.eval_condition(condition, env_id)? {
+            match self.resolve(body, env_id) {
+                Ok(_) | Err(InterpreterError::Continue) => {}
+                Err(InterpreterError::Break) => break,
+                Err(err) => return Err(err),
+            }
         }
 
-        let mut boolean = cast_value::<BoolValue>(&condition_innner).unwrap();
-        while boolean.value() {
-            self.resolve(body.clone(), env_id)?;
-
-            let condition_resolved = self.resolve(condition.clone(), env_id)?;
-            let condition_innner = condition_resolved
-                .lock()
-                .expect("while_statement: failed to get further condition");
-            if condition_innner.kind() != ValueType::Boolean {
-                bail!(InterpreterError::InvalidCondition(dyn_clone::clone_box(
-                    &**condition_innner
-                )))
+        Ok(Value::Null)
+    }
+
+    fn eval_for_in_statement(
+        &mut self,
+        left: &Node,
+        iterable: &Node,
+        body: &Node,
+        env_id: EnvironmentId,
+    ) -> Result<Value, InterpreterError> {
+        let variable_name = match left {
+            Node::Identifier(name) => name,
+            _ => bail!(InterpreterError::UnexpectedNode(Box::new(left.clone()))),
+        };
+        let items = get_items(self.resolve(iterable, env_id)?)?;
+
+        for item in items {
+            // every iteration gets its own binding of the loop variable
+            let iteration_env = self.scope_state.create_environment(Some(env_id));
+            let scope = self.scope_state.get_scope_mut(iteration_env.id()).unwrap();
+            scope.declare_variable(variable_name.clone(), item, false)?;
+
+            let result = self.resolve(body, iteration_env.id());
+            self.scope_state.release_environment(iteration_env);
+            match result {
+                Ok(_) | Err(InterpreterError::Continue) => {}
+                Err(InterpreterError::Break) => break,
+                Err(err) => return Err(err),
             }
-            boolean = cast_value::<BoolValue>(&condition_innner).unwrap();
         }
 
-        return Ok(Arc::new(Mutex::new(Box::new(NullValue::default()))));
+        Ok(Value::Null)
     }
 
-    fn eval_class_declaration(
+    fn eval_try_statement(
+        &mut self,
+        block: &Node,
+        handler: Option<&Node>,
+        finalizer: Option<&Node>,
+        env_id: EnvironmentId,
+    ) -> Result<Value, InterpreterError> {
+        let result = match (self.resolve(block, env_id), handler) {
+            (Err(err), Some(handler)) if err.is_catchable() => {
+                self.eval_catch_clause(handler, err, env_id)
+            }
+            (result, _) => result,
+        };
+
+        // finally runs for errors and control flow signals as well,
+        // an error inside of it replaces the original result
+        if let Some(finalizer) = finalizer {
+            self.resolve(finalizer, env_id)?;
+        }
+
+        result.map(|_| Value::Null)
+    }
+
+    fn eval_catch_clause(
+        &mut self,
+        handler: &Node,
+        error: InterpreterError,
+        env_id: EnvironmentId,
+    ) -> Result<Value, InterpreterError> {
+        let (parameter, body) = match handler {
+            Node::CatchClause(parameter, body) => (parameter, body),
+            node => bail!(InterpreterError::UnexpectedNode(Box::new(node.clone()))),
+        };
+
+        let catch_env = self.scope_state.create_environment(Some(env_id));
+        if let Some(parameter) = parameter {
+            let error = error.into_value();
+            let scope = self.scope_state.get_scope_mut(catch_env.id()).unwrap();
+            scope.declare_variable(parameter.clone(), error, false)?;
+        }
+
+        let result = self.resolve(body, catch_env.id());
+        self.scope_state.release_environment(catch_env);
+        result
+    }
+
+    fn eval_condition(
+        &mut self,
+        condition: &Node,
+        env_id: EnvironmentId,
+    ) -> Result<bool, InterpreterError> {
+        match self.resolve(condition, env_id)? {
+            Value::Boolean(condition) => Ok(condition),
+            condition => bail!(InterpreterError::InvalidCondition(condition)),
+        }
+    }
+
+    fn eval_import_declaration(
         &mut self,
-        name: String,
-        super_class: Option<Box<Node>>,
-        body: Vec<Box<Node>>,
+        names: &[String],
+        path: &str,
         env: EnvironmentId,
-    ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError> {
-        let mut class = ClassValue::default();
-        class.name = name.clone();
-        match super_class {
-            Some(id) => {
-                let resolved = self.resolve(id, env)?;
-                let target = resolved
-                    .lock()
-                    .expect("class_declaration: failed to resolve super class");
-                if target.kind() == ValueType::Class {
-                    let target_class = cast_value::<ClassValue>(&target).unwrap();
-                    class.copy_properties(&target_class);
-                    class.copy_methods(&target_class);
-                    class.super_class = Some(target_class);
-                }
+    ) -> Result<Value, InterpreterError> {
+        let module = self.load_module(path, env)?;
+        // `import "path"` brings in everything the module exports
+        let names = if names.is_empty() {
+            module.exports.clone()
+        } else {
+            names.to_vec()
+        };
+        for name in names {
+            let value = module.get_export(&self.scope_state, path, &name)?;
+            let scope = match self.scope_state.get_scope_mut(env) {
+                Some(scope) => scope,
+                None => bail!(InterpreterError::UnresolvedEnvironment(env)),
+            };
+            scope.declare_variable(name, value, true)?;
+        }
+        Ok(Value::Null)
+    }
+
+    fn eval_export_declaration(
+        &mut self,
+        declaration: &Node,
+        env: EnvironmentId,
+    ) -> Result<Value, InterpreterError> {
+        let name = match declaration {
+            Node::FunctionDeclaration(name, ..)
+            | Node::ClassDeclaration(name, ..)
+            | Node::VariableDeclaration(name, ..) => name.clone(),
+            _ => bail!(InterpreterError::UnexpectedNode(Box::new(
+                declaration.clone()
+            ))),
+        };
+        if let Some(module) = self.modules.stack.last_mut() {
+            // only top level declarations can be exported
+            if module.env.id() != env {
+                bail!(InterpreterError::UnexpectedNode(Box::new(
+                    declaration.clone()
+                )))
             }
-            None => {}
+            module.exports.push(name);
+        }
+        self.resolve(declaration, env)
+    }
+
+    fn eval_class_declaration(
+        &mut self,
+        name: &str,
+        super_class: Option<&Node>,
+        body: &[Box<Node>],
+        env: EnvironmentId,
+    ) -> Result<Value, InterpreterError> {
+        let mut class = ClassValue {
+            name: name.to_string(),
+            declaration_env: self.get_environment_handle(env)?,
+            ..Default::default()
         };
+        if let Some(id) = super_class {
+            if let Value::Class(target_class) = self.resolve(id, env)? {
+                // methods are looked up through the super class, see `ClassValue::get_method`
+                class.copy_properties(&target_class);
+                class.super_class = Some(target_class);
+            }
+        }
         for class_stmt in body {
-            match *class_stmt {
+            match &**class_stmt {
                 Node::PropertyDefinition(name, value, is_static) => {
                     class.insert_property(ClassProperty {
                         value: self.resolve(value, env)?,
-                        name,
-                        is_static,
+                        name: name.clone(),
+                        is_static: *is_static,
                     })
                 }
                 Node::MethodDefinition(name, method_params, body, is_static) => {
-                    let mut params: Vec<ClassMethodParameter> = vec![];
-                    for param in method_params {
-                        match *param {
-                            Node::Identifier(name) => params.push(ClassMethodParameter {
-                                name,
-                                default_value: None,
-                            }),
-                            // TODO: Add support for default value (Assignment Expression)
-                            _ => bail!(InterpreterError::UnexpectedNode(dyn_clone::clone_box(
-                                &*param
-                            ))),
-                        }
-                    }
+                    let params: Vec<ClassMethodParameter> = self
+                        .eval_parameters(method_params)?
+                        .into_iter()
+                        .map(|param| ClassMethodParameter {
+                            name: param.name,
+                            default_value: param.default_value,
+                            is_rest: param.is_rest,
+                        })
+                        .collect();
                     class.insert_method(ClassMethod {
-                        name,
-                        is_static,
+                        name: name.clone(),
+                        is_static: *is_static,
                         args: params,
-                        body: body,
+                        body: Arc::from(body.clone()),
                     })
                 }
                 _ => {}
             };
         }
-        let scope_c = SCOPE_STATE.clone();
-        let mut scope_state = scope_c
-            .lock()
-            .expect("class_declaration: failed to get mutable scope state");
-        let scope = match scope_state.get_scope_mut(env) {
+        // classes named after a builtin type extend it instead of declaring a variable
+        if Prototypes::is_extendable(name) {
+            let class = Arc::new(class);
+            self.prototypes.extend(class.clone());
+            return Ok(Value::Class(class));
+        }
+        let scope = match self.scope_state.get_scope_mut(env) {
             Some(scope) => scope,
             None => bail!(InterpreterError::UnresolvedEnvironment(env)),
         };
-        let result =
-            scope.declare_variable(name, Arc::new(Mutex::new(Box::new(class.clone()))), true)?;
-        drop(scope_state);
-        drop(scope_c);
+        let result = scope.declare_variable(name.to_string(), Value::from(class), true)?;
         Ok(result)
     }
 
     fn eval_member_expression(
         &mut self,
-        object: Box<Node>,
-        property: Box<Node>,
+        object: &Node,
+        property: &Node,
         computed: bool,
         env: EnvironmentId,
-    ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError> {
+    ) -> Result<Value, InterpreterError> {
         let object = self.resolve(object, env)?;
 
-        let property: Arc<Mutex<Box<dyn RuntimeValue>>> = if computed {
+        let property = if computed {
             self.resolve(property, env)?
         } else {
-            match *property {
-                Node::Identifier(value) => Arc::new(Mutex::new(Box::new(StringValue::from(value)))),
-                _ => bail!(InterpreterError::UnexpectedNode(property)),
+            match property {
+                Node::Identifier(value) => Value::from(value.clone()),
+                _ => bail!(InterpreterError::UnexpectedNode(Box::new(property.clone()))),
             }
         };
 
-        let prop = property.clone();
-        let property_inner = prop
-            .lock()
-            .expect("member_expression: failed to get property");
-        if property_inner.kind() != ValueType::String {
-            bail!(InterpreterError::UnsupportedValue(property))
-        }
+        let key = match property {
+            Value::Integer(index) => return get_element(object, index),
+            Value::String(key) => key.to_string(),
+            property => bail!(InterpreterError::UnsupportedValue(property)),
+        };
 
-        let key = cast_value::<StringValue>(&property_inner).unwrap().value();
-
-        let obj = object.clone();
-        let object_inner = obj.lock().expect("member_expression: failed to get object");
-        let value = match object_inner.kind() {
-            ValueType::Object => dyn_clone::clone_box(&**object_inner),
-            ValueType::Class => {
-                let class = cast_value::<ClassValue>(&object_inner).unwrap();
-                let class_prop = class.get_static_property(key.clone());
-                if class_prop.is_none() {
-                    let class_method = class.get_static_method(key.clone());
-                    if let Some(class_method) = class_method {
-                        let mut temp_map: HashMap<Key, Value> = HashMap::new();
-                        let func = FunctionValue::new(
-                            class_method.name,
-                            class_method
-                                .args
-                                .iter()
-                                .map(|arg| FunctionParameter {
-                                    name: arg.name.clone(),
-                                    default_value: arg.default_value.clone(),
-                                })
-                                .collect(),
-                            env,
-                            class_method.body,
-                        );
-                        temp_map.insert(key.clone(), Arc::new(Mutex::new(Box::new(func))));
-                        Box::new(ObjectValue::from(temp_map))
-                    } else {
-                        bail!(InterpreterError::UnresolvedProperty(key.clone()))
+        self.get_property(&object, &key)
+    }
+
+    /// Property `key` of an already evaluated object
+    fn get_property(&self, object: &Value, key: &str) -> Result<Value, InterpreterError> {
+        let fields = match object {
+            Value::Object(fields) => fields.clone(),
+            Value::ClassInstance(instance) => {
+                return Ok(self.get_instance_member(instance, key).unwrap_or_default())
+            }
+            Value::Class(class) => {
+                if let Some(class_prop) = class.get_static_property(key.to_string()) {
+                    return Ok(class_prop.value);
+                }
+                match class.get_static_method(key.to_string()) {
+                    Some(class_method) => {
+                        let func = class_method.to_function(class.declaration_env.clone());
+                        return Ok(Value::from(func));
                     }
-                } else {
-                    let mut temp_map: HashMap<Key, Value> = HashMap::new();
-                    temp_map.insert(key.clone(), class_prop.unwrap().value);
-                    Box::new(ObjectValue::from(temp_map))
+                    None => bail!(InterpreterError::UnresolvedProperty(key.to_string())),
                 }
             }
-            ValueType::String => {
-                let string_value = cast_value::<StringValue>(&object_inner).unwrap();
-                get_string_object(&string_value)
-            }
-            ValueType::Integer | ValueType::Decimal => {
-                let number = dyn_clone::clone_box(&**object_inner);
-                get_number_object(&number)
-            }
-            ValueType::Array => {
-                let array_value = cast_value::<ArrayValue>(&object_inner).unwrap();
-                get_array_object(&array_value, &key, env)?
-            }
-            _ => bail!(InterpreterError::UnexpectedValue(dyn_clone::clone_box(
-                &**object_inner
-            ))),
+            Value::String(_)
+            | Value::Integer(_)
+            | Value::Decimal(_)
+            | Value::Boolean(_)
+            | Value::Array(_) => {
+                // script extensions come first so they can override the builtin methods
+                let value = self
+                    .get_extension_method(object, key)
+                    .or_else(|| self.prototypes.get_property(object, key));
+                return Ok(value.unwrap_or_default());
+            }
+            _ => bail!(InterpreterError::UnexpectedValue(object.clone())),
         };
 
-        let object = cast_value::<ObjectValue>(&value).unwrap();
-
-        let map = object.map();
+        let value = fields
+            .get_property(key)
+            .or_else(|| self.get_extension_method(object, key));
+        Ok(value.unwrap_or_default())
+    }
 
-        if let Some(value) = map.get(&key) {
-            return Ok(value.clone());
-        }
+    /// Method of a class that extends the type of the value, see `Prototypes::extend`
+    fn get_extension_method(&self, value: &Value, key: &str) -> Option<Value> {
+        self.prototypes
+            .find_extension(value, |class| class.get_method(key))
+            .map(Value::from)
+    }
 
-        Ok(Arc::new(Mutex::new(Box::new(NullValue::default()))))
+    /// Own field of the instance, or the method of its class when there is no such field
+    fn get_instance_member(&self, instance: &ClassInstanceValue, key: &str) -> Option<Value> {
+        instance
+            .fields()
+            .get_property(key)
+            .or_else(|| instance.class().get_method(key).map(Value::from))
     }
 
     fn eval_unary_expression(
         &mut self,
-        expression: Box<Node>,
-        operator: UnaryOperator,
+        expression: &Node,
+        operator: &UnaryOperator,
         env: EnvironmentId,
-    ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError> {
-        let target = self.resolve(expression.clone(), env)?;
-        let result: Arc<Mutex<Box<dyn RuntimeValue>>> = match operator {
+    ) -> Result<Value, InterpreterError> {
+        let target = self.resolve(expression, env)?;
+        let result = match operator {
             UnaryOperator::Plus => target,
-            UnaryOperator::Minus => {
-                let target = target
-                    .lock()
-                    .expect("unary_expression: failed to get target for converting to negative");
-                match target.kind() {
-                    ValueType::Decimal => {
-                        let value = cast_value::<DecimalValue>(&target).unwrap();
-                        Arc::new(Mutex::new(Box::new(DecimalValue::from(
-                            value.value() * -1.0,
-                        ))))
-                    }
-                    ValueType::Integer => {
-                        let value = cast_value::<IntegerValue>(&target).unwrap();
-                        Arc::new(Mutex::new(Box::new(IntegerValue::from(value.value() * -1))))
-                    }
-                    _ => bail!(InterpreterError::UnexpectedNode(expression)),
-                }
-            }
-            UnaryOperator::Negation => {
-                let target = target
-                    .lock()
-                    .expect("unary_expression: failed to get target for negation");
-                match target.kind() {
-                    ValueType::Boolean => {
-                        let value = cast_value::<BoolValue>(&target).unwrap();
-                        Arc::new(Mutex::new(Box::new(BoolValue::from(!value.value()))))
-                    }
-                    _ => bail!(InterpreterError::UnexpectedNode(expression)),
-                }
-            }
-            _ => bail!(InterpreterError::UnsupportedUnaryOperator(operator)),
+            UnaryOperator::Minus => self.negate(target, || {
+                InterpreterError::UnexpectedNode(Box::new(expression.clone()))
+            })?,
+            UnaryOperator::Negation => match target {
+                Value::Boolean(value) => Value::from(!value),
+                _ => bail!(InterpreterError::UnexpectedNode(Box::new(
+                    expression.clone()
+                ))),
+            },
+            _ => bail!(InterpreterError::UnsupportedUnaryOperator(operator.clone())),
         };
         Ok(result)
     }
 
     fn eval_if_statement(
         &mut self,
-        condition: Box<Node>,
-        body: Box<Node>,
-        alternate: Option<Box<Node>>,
+        condition: &Node,
+        body: &Node,
+        alternate: Option<&Node>,
         env_id: EnvironmentId,
-    ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError> {
-        let condition = self.resolve(condition, env_id)?;
-        let condition = condition
-            .lock()
-            .expect("if_statement: failed to get condition");
-        if condition.kind() != ValueType::Boolean {
-            bail!(InterpreterError::InvalidCondition(dyn_clone::clone_box(
-                &**condition
-            )))
-        }
-
-        let boolean = cast_value::<BoolValue>(&condition).unwrap();
-        if boolean.value() {
+    ) -> Result<Value, InterpreterError> {
+        if self.eval_condition(condition, env_id)? {
             self.resolve(body, env_id)?;
         } else if let Some(alternate) = alternate {
             self.resolve(alternate, env_id)?;
         }
-        return Ok(Arc::new(Mutex::new(Box::new(NullValue::default()))));
+        Ok(Value::Null)
     }
 
     fn eval_function_declaration(
         &mut self,
-        name: String,
-        params: Vec<Box<Node>>,
-        body: Box<Node>,
+        name: &str,
+        params: &[Box<Node>],
+        body: &Node,
         env: EnvironmentId,
-    ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError> {
+    ) -> Result<Value, InterpreterError> {
+        let parameters = self.eval_parameters(params)?;
+        let function = FunctionValue::new(
+            name.to_string(),
+            parameters,
+            self.get_environment_handle(env)?,
+            Arc::new(body.clone()),
+        );
+
+        let scope = match self.scope_state.get_scope_mut(env) {
+            Some(scope) => scope,
+            None => bail!(InterpreterError::UnresolvedEnvironment(env)),
+        };
+
+        let value = scope.declare_variable(name.to_string(), Value::from(function), true)?;
+
+        Ok(value)
+    }
+
+    fn eval_function_expression(
+        &mut self,
+        params: &[Box<Node>],
+        body: &Node,
+        env: EnvironmentId,
+    ) -> Result<Value, InterpreterError> {
+        let parameters = self.eval_parameters(params)?;
+        // closures keep the environment they were created in, like declared functions
+        let function = FunctionValue::new(
+            "anonymous".into(),
+            parameters,
+            self.get_environment_handle(env)?,
+            Arc::new(body.clone()),
+        );
+        Ok(Value::from(function))
+    }
+
+    fn eval_parameters(
+        &self,
+        params: &[Box<Node>],
+    ) -> Result<Vec<FunctionParameter>, InterpreterError> {
         let mut parameters: Vec<FunctionParameter> = vec![];
         for parameter in params {
-            let parameter_clone = dyn_clone::clone_box(&*parameter);
-            match *parameter {
+            let parameter_clone = dyn_clone::clone_box(&**parameter);
+            // rest parameter takes all of the remaining arguments, so it has to be the last one
+            if parameters.last().is_some_and(|previous| previous.is_rest) {
+                bail!(InterpreterError::InvalidFunctionParameter(parameter_clone))
+            }
+            match *parameter_clone.clone() {
                 Node::Identifier(value) => {
+                    // don't allow required args after default ones
+                    // e.g. _(arg1 = null, arg2, arg3) - invalid
+                    // e.g. _(arg1, arg2 = null, arg3) - invalid
+                    //      _(arg1, arg2, arg3 = null) - valid
+                    if let Some(previous) = parameters.last() {
+                        if previous.default_value.is_some() {
+                            bail!(InterpreterError::InvalidDefaultParameter(
+                                previous.name.clone()
+                            ))
+                        }
+                    }
                     parameters.push(FunctionParameter::new(value, None));
                 }
                 Node::AssignmentExpression(left, operator, right) => {
@@ -471,1050 +635,513 @@ impl Interpreter {
                         bail!(InterpreterError::InvalidFunctionParameter(parameter_clone))
                     }
                     let left_clone = dyn_clone::clone_box(&*left);
-                    if let Node::Identifier(value) = *left {
-                        parameters.push(FunctionParameter::new(
-                            value,
-                            Some(self.resolve(right, env)?),
-                        ));
+                    match *left {
+                        Node::Identifier(value) => {
+                            parameters.push(FunctionParameter::new(value, Some(right)))
+                        }
+                        _ => bail!(InterpreterError::InvalidAssignFactor(left_clone)),
                     }
-                    bail!(InterpreterError::InvalidAssignFactor(left_clone))
                 }
-                _ => bail!(InterpreterError::InvalidFunctionParameter(parameter)),
+                Node::RestElement(name) => parameters.push(FunctionParameter::rest(name)),
+                _ => bail!(InterpreterError::InvalidFunctionParameter(parameter_clone)),
             }
         }
-        let function = FunctionValue::new(name.clone(), parameters, env, body);
+        Ok(parameters)
+    }
 
-        let scope_c = SCOPE_STATE.clone();
-        let mut scope_state = scope_c
-            .lock()
-            .expect("function_declaration: failed to get mutable scope state");
-        let scope = match scope_state.get_scope_mut(env) {
-            Some(scope) => scope,
-            None => bail!(InterpreterError::UnresolvedEnvironment(env)),
-        };
+    fn eval_arguments(
+        &mut self,
+        parameters: &[FunctionParameter],
+        args: &[Value],
+        env_id: EnvironmentId,
+    ) -> Result<(), InterpreterError> {
+        let required_args = parameters
+            .iter()
+            .filter(|parameter| parameter.default_value.is_none() && !parameter.is_rest)
+            .count();
+        let is_variadic = parameters.iter().any(|parameter| parameter.is_rest);
+        check_argument_count(required_args, parameters.len(), is_variadic, args.len())?;
+        for (index, parameter) in parameters.iter().enumerate() {
+            let value = match (args.get(index), &parameter.default_value) {
+                _ if parameter.is_rest => {
+                    let rest = args.get(index..).unwrap_or_default().to_vec();
+                    Value::from(ArrayValue::from(rest))
+                }
+                (Some(arg), _) => arg.clone(),
+                // defaults are evaluated in the callee environment on every call,
+                // so they can refer to previous parameters and to self
+                (None, Some(default_value)) => self.resolve(default_value, env_id)?,
+                (None, None) => bail!(InterpreterError::InvalidParameterCount(
+                    required_args,
+                    args.len()
+                )),
+            };
+            let scope = match self.scope_state.get_scope_mut(env_id) {
+                Some(scope) => scope,
+                None => bail!(InterpreterError::UnresolvedEnvironment(env_id)),
+            };
+            scope.declare_variable(parameter.name.clone(), value, true)?;
+        }
+        Ok(())
+    }
 
-        let value =
-            scope.declare_variable(name.clone(), Arc::new(Mutex::new(Box::new(function))), true)?;
+    /// Binds the arguments in the call environment and runs the body
+    fn eval_function_call(
+        &mut self,
+        func: &FunctionValue,
+        args: &[Value],
+        env: EnvironmentId,
+    ) -> Result<Value, InterpreterError> {
+        self.eval_arguments(&func.parameters, args, env)?;
+        self.eval_function_body(&func.body, env)
+    }
 
-        Ok(value)
+    fn eval_function_body(
+        &mut self,
+        body: &Node,
+        env: EnvironmentId,
+    ) -> Result<Value, InterpreterError> {
+        match self.resolve(body, env) {
+            Ok(_) => Ok(Value::Null),
+            Err(InterpreterError::Return(value)) => Ok(value),
+            // loop control must not leak into the loop the function was called from
+            Err(InterpreterError::Break) => {
+                bail!(InterpreterError::InvalidLoopControl("break".to_string()))
+            }
+            Err(InterpreterError::Continue) => {
+                bail!(InterpreterError::InvalidLoopControl("continue".to_string()))
+            }
+            Err(err) => Err(err),
+        }
     }
 
     fn eval_class_call(
         &mut self,
-        object: Box<Node>,
-        // obj_val: Box<dyn RuntimeValue>,
-        callee_inner: Box<dyn RuntimeValue>,
-        args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>,
-        env: EnvironmentId,
-    ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError> {
-        let obj_value = self.resolve(object.clone(), env)?;
-        let obj_val = obj_value
-            .lock()
-            .expect("class_call: failed to resolve object");
-        if callee_inner.kind() != ValueType::Function {
-            bail!(InterpreterError::InvalidFunctionCallee(Arc::new(
-                Mutex::new(callee_inner)
-            )))
+        object: Value,
+        func: &FunctionValue,
+        args: Vec<Value>,
+    ) -> Result<Value, InterpreterError> {
+        // the environment is released once the call is over, unless something captured it
+        let frame = self
+            .scope_state
+            .create_environment(Some(func.declaration_env.id()));
+        let env_id = frame.id();
+        let scope = self.scope_state.get_scope_mut(env_id).unwrap();
+        // objects are shared by reference, so changes to self are visible to the caller
+        scope.declare_variable("self".into(), object, false)?;
+        if let Some(class) = &func.class {
+            // hidden binding to resolve `super` against, see `eval_super_call`
+            scope.declare_variable("__class__".into(), Value::Class(class.clone()), true)?;
         }
-        let func = cast_value::<FunctionValue>(&callee_inner).unwrap();
-
-        let mut scope_state = SCOPE_STATE
-            .lock()
-            .expect("class_call: failed to get mutable scope state");
-        let env_id = scope_state.create_environment(Some(func.declaration_env));
-        let scope = scope_state.get_scope_mut(env_id).unwrap();
-        scope.declare_variable(
-            "self".into(),
-            Arc::new(Mutex::new(dyn_clone::clone_box(&**obj_val))),
-            false,
-        )?;
-        let init_args = func.parameters.clone();
-        // don't allow default value for first args
-        // e.g. _(arg1 = null, arg2, arg3) - invalid
-        // e.g. _(arg1, arg2 = null, arg3) - invalid
-        //      _(arg1, arg2, arg3 = null) - valid
-        for (index, arg) in init_args.iter().enumerate() {
-            if arg.default_value.is_some() && index + 1 < init_args.len() {
-                bail!(InterpreterError::InvalidDefaultParameter(arg.name.clone()))
+        let result = self.eval_function_call(func, &args, env_id);
+        self.scope_state.release_environment(frame);
+        result
+    }
+
+    /// Calls a method of the super class with the current `self`,
+    /// `super(...)` calls the constructor
+    fn eval_super_call(
+        &mut self,
+        method_name: String,
+        args: Vec<Value>,
+        env: EnvironmentId,
+    ) -> Result<Value, InterpreterError> {
+        let scope = match self.scope_state.get_scope(env) {
+            Some(scope) => scope,
+            None => bail!(InterpreterError::UnresolvedEnvironment(env)),
+        };
+        let class = scope.lookup_variable_safe("__class__", &self.scope_state);
+        let object = scope.lookup_variable_safe("self", &self.scope_state);
+
+        match (class, object) {
+            (Some(Value::Class(class)), Some(object)) => {
+                self.call_super(&class, object, &method_name, args)
             }
+            _ => bail!(InterpreterError::InvalidSuper),
         }
-        let required_args = init_args
-            .iter()
-            .filter(|arg| arg.default_value.is_none())
-            .count();
-        if args.len() < required_args {
-            bail!(InterpreterError::InvalidParameterCount(
-                required_args,
-                args.len()
-            ))
-        }
-        for (index, parameter) in func.parameters.iter().enumerate() {
-            let value = args.get(index).unwrap().clone();
-            scope.declare_variable(parameter.name.clone(), value, true)?;
-        }
-        drop(scope_state);
-        let value = self.resolve(func.body, env_id)?;
-        let scope_state = SCOPE_STATE
-            .lock()
-            .expect("class_call: failed to get scope state");
-        let scope = scope_state.get_scope(env_id).unwrap();
-        let new_self = scope.lookup_variable("self".into(), &scope_state)?;
-        drop(scope_state);
-        let mut assigne = Some(object.clone());
-        let mut assign_value = new_self;
-        while let Some(ref assignee) = assigne {
-            match *dyn_clone::clone_box(&**assignee) {
-                Node::Identifier(var_name) => {
-                    self.assign_variable(var_name, assign_value.clone(), env, true)?;
-                    assigne = None;
-                }
-                Node::MemberExpression(parent, current, computed) => {
-                    let prop: Arc<Mutex<Box<dyn RuntimeValue>>> = if computed {
-                        self.resolve(current, env)?
-                    } else {
-                        match *current {
-                            Node::Identifier(value) => {
-                                Arc::new(Mutex::new(Box::new(StringValue::from(value))))
-                            }
-                            _ => bail!(InterpreterError::UnsupportedNode(current)),
-                        }
-                    };
-                    let prop_inner = prop
-                        .lock()
-                        .expect("class_call: failed to get prop of member expression");
-                    if prop_inner.kind() != ValueType::String {
-                        bail!(InterpreterError::UnsupportedValue(Arc::new(Mutex::new(
-                            dyn_clone::clone_box(&**prop_inner)
-                        ))))
-                    }
-                    let prop_str = cast_value::<StringValue>(&prop_inner).unwrap();
-
-                    let obj_val = self.resolve(parent.clone(), env)?;
-                    let obj_inner = obj_val.lock().expect("class_call: failed to get parent");
-                    if obj_inner.kind() == ValueType::Object {
-                        let mut obj = cast_value::<ObjectValue>(&obj_inner).unwrap();
-                        obj.assign_property(prop_str.value(), assign_value.clone());
-                        assigne = Some(parent);
-                        assign_value = Arc::new(Mutex::new(obj));
-                    } else {
-                        // TODO: look if this correct to look only for object values
-                        bail!(InterpreterError::UnexpectedNode(object.clone()))
-                    }
-                }
-                node => bail!(InterpreterError::UnexpectedNode(Box::new(node))),
-            };
-        }
-        Ok(value)
     }
 
     fn eval_call_expression(
         &mut self,
-        callee: Box<Node>,
-        arguments: Vec<Box<Node>>,
+        callee: &Node,
+        arguments: &[Box<Node>],
         env: EnvironmentId,
-    ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError> {
-        let mut args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>> = vec![];
+    ) -> Result<Value, InterpreterError> {
+        let mut args: Vec<Value> = vec![];
         for arg in arguments {
             let value = self.resolve(arg, env)?;
             args.push(value);
         }
-        if let Node::MemberExpression(object, property, _computed) =
-            *dyn_clone::clone_box(&*callee.clone())
-        {
-            let object_value = self.resolve(object.clone(), env)?;
-            let obj = object_value
-                .lock()
-                .expect("call_expression: failed to get object from member expression");
-            if let Node::Identifier(func_name) = *property {
-                // TODO: add other extendable types, e.g. string
-                if obj.kind() == ValueType::Object || obj.kind() == ValueType::Array {
-                    let class_obj = match obj.kind() {
-                        ValueType::Object => cast_value::<ObjectValue>(&obj).unwrap(),
-                        ValueType::Array => {
-                            let arr = cast_value::<ArrayValue>(&obj).unwrap();
-                            get_array_object(&arr, &func_name, env)?
-                        }
-                        _ => bail!(InterpreterError::UnexpectedValue(dyn_clone::clone_box(
-                            &**obj
-                        ))),
-                    };
-                    let calle = class_obj.get_property(func_name.clone());
-                    if calle.is_none() {
-                        bail!(InterpreterError::UnresolvedProperty(func_name.clone()))
-                    }
-                    let calle_res = calle.unwrap();
-                    let fn_callee = calle_res
-                        .lock()
-                        .expect("call_expression: failed to get class method as function");
-                    if fn_callee.kind() == ValueType::Function {
-                        return Ok(self.eval_class_call(
-                            object,
-                            // dyn_clone::clone_box(&*class_obj),
-                            dyn_clone::clone_box(&**fn_callee),
-                            args,
-                            env,
-                        )?);
-                    }
-                }
-            }
-        }
-        let fn_callee = self.resolve(callee, env)?;
-
-        let fn_calle_c = fn_callee.clone();
-        let fn_callee_box = fn_calle_c
-            .lock()
-            .expect("call_expression: failed to get function calle");
-        let result: Arc<Mutex<Box<dyn RuntimeValue>>> = match fn_callee_box.kind().clone() {
-            ValueType::NativeFn => {
-                let native_fn = match fn_callee_box.into_any().downcast::<NativeFnValue>() {
-                    Ok(native_fn) => native_fn,
-                    Err(_) => bail!(InterpreterError::UnsupportedValue(fn_callee.clone())),
-                };
-                let native_fn_c = dyn_clone::clone_box(&*native_fn);
-                drop(native_fn);
-                drop(fn_callee_box);
-                drop(fn_callee);
-
-                native_fn_c.callee().run(args.clone())
+        match callee {
+            Node::Identifier(name) if name == "super" => {
+                self.eval_super_call("__new__".into(), args, env)
             }
-            ValueType::Function => {
-                let func = match fn_callee_box.into_any().downcast::<FunctionValue>() {
-                    Ok(func) => func,
-                    Err(_) => bail!(InterpreterError::UnsupportedValue(fn_callee.clone())),
-                };
-                let func_c = dyn_clone::clone_box(&*func);
-                drop(func);
-                drop(fn_callee_box);
-                drop(fn_callee);
-                let mut scope_state = SCOPE_STATE
-                    .lock()
-                    .expect("call_expression: failed to get scope state for function");
-                let env_id = scope_state.create_environment(Some(func_c.declaration_env));
-                let scope = scope_state.get_scope_mut(env_id).unwrap();
-                let init_args = func_c.parameters.clone();
-                // don't allow default value for first args
-                // e.g. _(arg1 = null, arg2, arg3) - invalid
-                // e.g. _(arg1, arg2 = null, arg3) - invalid
-                //      _(arg1, arg2, arg3 = null) - valid
-                for (index, arg) in init_args.iter().enumerate() {
-                    if arg.default_value.is_some() && index + 1 < init_args.len() {
-                        bail!(InterpreterError::InvalidDefaultParameter(arg.name.clone()))
-                    }
+            Node::MemberExpression(object, property, false) => match (&**object, &**property) {
+                (Node::Identifier(object_name), Node::Identifier(method_name))
+                    if object_name == "super" =>
+                {
+                    self.eval_super_call(method_name.clone(), args, env)
                 }
-                let required_args = init_args
-                    .iter()
-                    .filter(|arg| arg.default_value.is_none())
-                    .count();
-                if args.len() < required_args {
-                    bail!(InterpreterError::InvalidParameterCount(
-                        required_args,
-                        args.len()
-                    ))
+                (object, Node::Identifier(method_name)) => {
+                    let object = self.resolve(object, env)?;
+                    self.eval_method_call(object, method_name, args)
                 }
-                for (index, parameter) in func_c.parameters.iter().enumerate() {
-                    let value = args.get(index).unwrap().clone();
-                    scope.declare_variable(parameter.name.clone(), value, true)?;
+                _ => {
+                    let callee = self.resolve(callee, env)?;
+                    self.call_value(callee, args)
                 }
-                drop(scope_state);
-                let value = self.resolve(func_c.body, env_id)?;
-                value
-                // Arc::new(Mutex::new(Box::new(NullValue::default())))
+            },
+            _ => {
+                let callee = self.resolve(callee, env)?;
+                self.call_value(callee, args)
             }
-            ValueType::Class => {
-                let class = match fn_callee_box.into_any().downcast::<ClassValue>() {
-                    Ok(class) => class,
-                    Err(_) => bail!(InterpreterError::UnsupportedValue(fn_callee.clone())),
-                };
-                drop(fn_callee_box);
-                drop(fn_callee);
-                let methods = class.methods;
-                let properties = class.properties;
-
-                let mut instance_map: HashMap<Key, Value> = HashMap::new();
-                for property in properties.iter().filter(|prop| !prop.is_static) {
-                    instance_map.insert(property.name.clone(), property.value.clone());
-                }
+        }
+    }
 
-                for (method_name, method) in
-                    methods.clone().iter().filter(|method| !method.1.is_static)
+    /// Calls the method `name` of an already evaluated receiver
+    fn eval_method_call(
+        &mut self,
+        object: Value,
+        name: &str,
+        args: Vec<Value>,
+    ) -> Result<Value, InterpreterError> {
+        let callee = match &object {
+            Value::Object(fields) => fields
+                .get_property(name)
+                .or_else(|| self.get_extension_method(&object, name)),
+            Value::ClassInstance(instance) => self.get_instance_member(instance, name),
+            Value::String(_)
+            | Value::Integer(_)
+            | Value::Decimal(_)
+            | Value::Boolean(_)
+            | Value::Array(_) => {
+                // script extensions come first so they can override the builtin methods
+                let extension = self.get_extension_method(&object, name);
+                // builtin methods get the receiver as their first argument
+                if let (None, Some(Value::NativeFn(method))) =
+                    (&extension, self.prototypes.get_method(&object, name))
                 {
-                    if method_name == "__new__" {
-                        continue;
-                    }
-                    let function = FunctionValue::new(
-                        method_name.clone(),
-                        method
-                            .args
-                            .iter()
-                            .map(|arg| {
-                                FunctionParameter::new(arg.name.clone(), arg.default_value.clone())
-                            })
-                            .collect(),
-                        env,
-                        method.clone().body,
-                    );
-                    instance_map.insert(
-                        method_name.clone(),
-                        Arc::new(Mutex::new(Box::new(function))),
-                    );
-                }
-
-                if methods.contains_key(&String::from("__new__")) {
-                    let constructor = methods.get(&String::from("__new__")).unwrap();
-                    let init_args = constructor.args.clone();
-                    // don't allow default value for first args
-                    // e.g. _(arg1 = null, arg2, arg3) - invalid
-                    // e.g. _(arg1, arg2 = null, arg3) - invalid
-                    //      _(arg1, arg2, arg3 = null) - valid
-                    for (index, arg) in init_args.iter().enumerate() {
-                        if arg.default_value.is_some() && index + 1 < init_args.len() {
-                            bail!(InterpreterError::InvalidDefaultParameter(arg.name.clone()))
-                        }
-                    }
-                    let required_args = init_args
-                        .iter()
-                        .filter(|arg| arg.default_value.is_none())
-                        .count();
-                    if args.len() < required_args {
-                        bail!(InterpreterError::InvalidParameterCount(
-                            required_args,
-                            args.len()
-                        ))
-                    }
-
-                    let obj = ObjectValue::from(instance_map);
-                    let mut scope_state = SCOPE_STATE
-                        .lock()
-                        .expect("call_expression: failed to get scope state from class object");
-                    // TODO: look if we use correct environment
-                    let env_id = scope_state.create_environment(Some(env));
-                    let scope = scope_state.get_scope_mut(env_id).unwrap();
-                    for (index, arg) in args.iter().enumerate() {
-                        if let Some(init_arg) = init_args.get(index) {
-                            scope.declare_variable(init_arg.name.clone(), arg.clone(), true)?;
-                        }
-                    }
-                    scope.declare_variable(
-                        "self".into(),
-                        Arc::new(Mutex::new(Box::new(obj.clone()))),
-                        true,
-                    )?;
-                    if let Some(super_class) = class.super_class {
-                        let super_constructor = super_class.methods.get("__new__");
-                        if super_constructor.is_some() {
-                            let super_constructor = super_constructor.unwrap();
-                            let super_init_args = super_constructor.args.clone();
-
-                            // don't allow default value for first args
-                            // e.g. _(arg1 = null, arg2, arg3) - invalid
-                            // e.g. _(arg1, arg2 = null, arg3) - invalid
-                            //      _(arg1, arg2, arg3 = null) - valid
-                            for (index, arg) in super_init_args.iter().enumerate() {
-                                if arg.default_value.is_some() && index + 1 < super_init_args.len()
-                                {
-                                    bail!(InterpreterError::InvalidDefaultParameter(
-                                        arg.name.clone()
-                                    ))
-                                }
-                            }
-
-                            let super_func = FunctionValue::new(
-                                "super".into(),
-                                super_init_args
-                                    .iter()
-                                    .map(|arg| FunctionParameter {
-                                        name: arg.name.clone(),
-                                        default_value: arg.default_value.clone(),
-                                    })
-                                    .collect(),
-                                env_id,
-                                super_constructor.body.clone(),
-                            );
-
-                            scope.declare_variable(
-                                "super".into(),
-                                Arc::new(Mutex::new(Box::new(super_func))),
-                                true,
-                            )?;
-                        }
-                    }
-                    drop(scope_state);
-                    self.resolve(constructor.body.clone(), env_id)?;
-                    let scope_state = SCOPE_STATE.lock().expect("call_expression: failed to get immutable scope state after class method call");
-                    let scope = scope_state.get_scope(env_id).unwrap();
-                    let value = scope.lookup_variable("self".into(), &scope_state)?;
-                    value
-                } else {
-                    Arc::new(Mutex::new(Box::new(ObjectValue::from(instance_map))))
+                    let method = method.clone();
+                    return method.callee().run(self, [vec![object], args].concat());
                 }
+                extension
+            }
+            _ => {
+                let callee = self.get_property(&object, name)?;
+                return self.call_value(callee, args);
             }
-            _ => bail!(InterpreterError::InvalidFunctionCallee(fn_callee.clone())),
         };
-        Ok(result)
+        match callee {
+            Some(Value::Function(method)) => self.eval_class_call(object, &method, args),
+            Some(callee) => self.call_value(callee, args),
+            None => bail!(InterpreterError::UnresolvedProperty(name.to_string())),
+        }
     }
 
-    fn convert_value_to_node(&self, value: Box<dyn RuntimeValue>) -> Box<Node> {
-        let node = match value.kind() {
-            ValueType::Boolean => {
-                let boolean = cast_value::<BoolValue>(&value).unwrap();
-                Node::Identifier(if boolean.value() {
-                    "true".to_string()
-                } else {
-                    "false".to_string()
-                })
-            }
-            ValueType::Decimal => {
-                let decimal = cast_value::<DecimalValue>(&value).unwrap();
-                Node::DecimalLiteral(decimal.value())
-            }
-            ValueType::Integer => {
-                let integer = cast_value::<IntegerValue>(&value).unwrap();
-                if integer.value() < 0 {
-                    Node::UnaryExpression(
-                        Box::new(Node::IntegerLiteral(integer.value().abs() as usize)),
-                        UnaryOperator::Minus,
-                    )
-                } else {
-                    Node::IntegerLiteral(integer.value().abs() as usize)
-                }
-            }
-            ValueType::Null => Node::Identifier("null".to_string()),
-            ValueType::Function | ValueType::NativeFn => Node::Identifier(stringify(value)),
-            ValueType::String => {
-                let string = cast_value::<StringValue>(&value).unwrap();
-                Node::StringLiteral(string.value())
-            }
-            ValueType::Array => {
-                let array = cast_value::<ArrayValue>(&value).unwrap();
-                Node::ArrayExpression(
-                    array
-                        .value()
-                        .into_iter()
-                        .map(|x| {
-                            self.convert_value_to_node(dyn_clone::clone_box(
-                                &**x.lock().expect("convert_value: failed to get array item"),
-                            ))
-                        })
-                        .collect::<Vec<Box<Node>>>(),
-                )
-            }
-            // TODO: throw InvalidOperand error instead
-            _ => Node::Identifier("null".to_string()),
+    fn call_value(&mut self, callee: Value, args: Vec<Value>) -> Result<Value, InterpreterError> {
+        let result = match callee {
+            Value::NativeFn(native_fn) => native_fn.callee().run(self, args)?,
+            Value::Function(func) => {
+                let frame = self
+                    .scope_state
+                    .create_environment(Some(func.declaration_env.id()));
+                let result = self.eval_function_call(&func, &args, frame.id());
+                self.scope_state.release_environment(frame);
+                result?
+            }
+            Value::Class(class) => self.instantiate(class, args)?,
+            callee => bail!(InterpreterError::InvalidFunctionCallee(callee)),
         };
-        Box::new(node)
+        Ok(result)
     }
 
     fn eval_logical_expression(
         &mut self,
-        left: Box<Node>,
-        operator: LogicalOperator,
-        right: Box<Node>,
+        left: &Node,
+        operator: &LogicalOperator,
+        right: &Node,
         env: EnvironmentId,
-    ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError> {
-        match operator {
-            LogicalOperator::And => {
-                let left = self.resolve(left, env)?;
-                let right = self.resolve(right, env)?;
-                let left_value = dyn_clone::clone_box(
-                    &**left
-                        .lock()
-                        .expect("logical_expression: failed to get left side for AND operator"),
-                );
-                let right_value = dyn_clone::clone_box(
-                    &**right
-                        .lock()
-                        .expect("logical_expression: failed to get right side for AND operator"),
-                );
-                if left_value.kind() != ValueType::Boolean {
-                    bail!(InterpreterError::InvalidValue(
-                        left_value,
-                        "boolean".to_string()
-                    ))
-                }
-                if left_value.kind() != ValueType::Boolean
-                    || right_value.kind() != ValueType::Boolean
-                {
-                    bail!(InterpreterError::InvalidValue(
-                        right_value,
-                        "boolean".to_string()
-                    ))
-                }
-                let left_bool = cast_value::<BoolValue>(&left_value).unwrap();
-                let right_bool = cast_value::<BoolValue>(&right_value).unwrap();
-                return Ok(Arc::new(Mutex::new(Box::new(BoolValue::from(
-                    left_bool.value() && right_bool.value(),
-                )))));
-            }
-            LogicalOperator::Or => {
-                let left = self.resolve(left, env)?;
-                let right = self.resolve(right, env)?;
-                let left_value = dyn_clone::clone_box(
-                    &**left
-                        .lock()
-                        .expect("logical_expression: failed to get left side for OR operator"),
-                );
-                let right_value = dyn_clone::clone_box(
-                    &**right
-                        .lock()
-                        .expect("logical_expression: failed to get right side for OR operator"),
-                );
-                if left_value.kind() != ValueType::Boolean {
-                    bail!(InterpreterError::InvalidValue(
-                        left_value,
-                        "boolean".to_string()
-                    ))
-                }
-                if left_value.kind() != ValueType::Boolean
-                    || right_value.kind() != ValueType::Boolean
-                {
-                    bail!(InterpreterError::InvalidValue(
-                        right_value,
-                        "boolean".to_string()
-                    ))
-                }
-                let left_bool = cast_value::<BoolValue>(&left_value).unwrap();
-                let right_bool = cast_value::<BoolValue>(&right_value).unwrap();
-                return Ok(Arc::new(Mutex::new(Box::new(BoolValue::from(
-                    left_bool.value() || right_bool.value(),
-                )))));
-            }
+    ) -> Result<Value, InterpreterError> {
+        let left_bool = self.eval_logical_operand(left, env)?;
+        // the right side is only evaluated if the left side doesn't decide the result
+        let result = match operator {
+            LogicalOperator::And => left_bool && self.eval_logical_operand(right, env)?,
+            LogicalOperator::Or => left_bool || self.eval_logical_operand(right, env)?,
+        };
+        Ok(Value::from(result))
+    }
+
+    fn eval_logical_operand(
+        &mut self,
+        operand: &Node,
+        env: EnvironmentId,
+    ) -> Result<bool, InterpreterError> {
+        match self.resolve(operand, env)? {
+            Value::Boolean(operand) => Ok(operand),
+            operand => bail!(InterpreterError::InvalidValue(
+                operand,
+                "boolean".to_string()
+            )),
+        }
+    }
+
+    fn get_environment_handle(
+        &self,
+        env: EnvironmentId,
+    ) -> Result<EnvironmentHandle, InterpreterError> {
+        match self.scope_state.get_handle(env) {
+            Some(handle) => Ok(handle),
+            None => bail!(InterpreterError::UnresolvedEnvironment(env)),
         }
     }
 
     fn assign_variable(
         &mut self,
-        name: String,
-        value: Arc<Mutex<Box<dyn RuntimeValue>>>,
+        name: &str,
+        value: Value,
         env: EnvironmentId,
         ignore_constant: bool,
-    ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError> {
-        let mut scope_state = SCOPE_STATE
-            .lock()
-            .expect("assign_variable: failed to get scope state");
-        Ok(scope_state.assign_variable(name, value, env, ignore_constant)?)
+    ) -> Result<Value, InterpreterError> {
+        if name == "self" {
+            return self.assign_receiver(value, env);
+        }
+        self.scope_state
+            .assign_variable(name, value, env, ignore_constant)
+    }
+
+    /// `self = value` in a method. The caller still points to the receiver,
+    /// so the contents of the receiver are replaced instead of the variable
+    fn assign_receiver(
+        &mut self,
+        value: Value,
+        env: EnvironmentId,
+    ) -> Result<Value, InterpreterError> {
+        let scope = match self.scope_state.get_scope(env) {
+            Some(scope) => scope,
+            None => bail!(InterpreterError::UnresolvedEnvironment(env)),
+        };
+        let receiver = scope.lookup_variable("self", &self.scope_state)?;
+        if !receiver.replace_contents(&value) {
+            bail!(InterpreterError::InvalidSelfAssignment(receiver, value))
+        }
+        Ok(receiver)
+    }
+
+    /// Name of the property on the left side of an assignment, e.g. `a.b = ...` or `a["b"] = ...`
+    fn eval_property_name(
+        &mut self,
+        property: &Node,
+        computed: bool,
+        env: EnvironmentId,
+    ) -> Result<Key, InterpreterError> {
+        let property = if computed {
+            self.resolve(property, env)?
+        } else {
+            match property {
+                Node::Identifier(value) => return Ok(value.clone()),
+                _ => bail!(InterpreterError::UnsupportedNode(Box::new(
+                    property.clone()
+                ))),
+            }
+        };
+        match property {
+            Value::String(name) => Ok(name.to_string()),
+            property => bail!(InterpreterError::UnsupportedValue(property)),
+        }
+    }
+
+    fn eval_property_assignment(
+        &mut self,
+        object: ObjectValue,
+        property: &Node,
+        computed: bool,
+        operator: &AssignmentOperator,
+        right: &Node,
+        env: EnvironmentId,
+    ) -> Result<Value, InterpreterError> {
+        let prop_name = self.eval_property_name(property, computed, env)?;
+        let value = if *operator == AssignmentOperator::Equals {
+            self.resolve(right, env)?
+        } else {
+            let previous_value = match object.get_property(&prop_name) {
+                Some(previous_value) => previous_value,
+                None => bail!(InterpreterError::UnresolvedProperty(prop_name)),
+            };
+            let binary_op = get_binary_operator(operator.clone());
+            let right = self.resolve(right, env)?;
+            self.eval_binary_values(previous_value, right, binary_op)?
+        };
+        // objects are shared by reference, so there is nothing to write back
+        object.assign_property(prop_name, value.clone());
+        Ok(value)
     }
 
     fn eval_assignment_expression(
         &mut self,
-        left: Box<Node>,
-        operator: AssignmentOperator,
-        right: Box<Node>,
+        left: &Node,
+        operator: &AssignmentOperator,
+        right: &Node,
         env: EnvironmentId,
-    ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError> {
-        if let Node::MemberExpression(object, property, computed) = *left {
-            let obj_val = self.resolve(object.clone(), env)?;
-            let obj_inner = obj_val
-                .lock()
-                .expect("assignment_expression: failed to get member expression's object");
-            match obj_inner.kind() {
-                ValueType::Object => {
-                    let mut obj = cast_value::<ObjectValue>(&obj_inner).unwrap();
-                    let prop: Arc<Mutex<Box<dyn RuntimeValue>>> = if computed {
-                        self.resolve(property, env)?
+    ) -> Result<Value, InterpreterError> {
+        if let Node::MemberExpression(object, property, computed) = left {
+            let computed = *computed;
+            match self.resolve(object, env)? {
+                Value::Object(obj) => {
+                    self.eval_property_assignment(obj, property, computed, operator, right, env)
+                }
+                Value::ClassInstance(instance) => self.eval_property_assignment(
+                    instance.fields().clone(),
+                    property,
+                    computed,
+                    operator,
+                    right,
+                    env,
+                ),
+                Value::Class(class) => {
+                    let prop_name = self.eval_property_name(property, computed, env)?;
+                    let value = if *operator == AssignmentOperator::Equals {
+                        self.resolve(right, env)?
                     } else {
-                        match *property {
-                            Node::Identifier(value) => {
-                                Arc::new(Mutex::new(Box::new(StringValue::from(value))))
-                            }
-                            _ => bail!(InterpreterError::UnsupportedNode(property)),
-                        }
-                    };
-                    let prop_inner = prop.lock().expect(
-                        "assignment_expression: failed to get property of member expression",
-                    );
-                    if prop_inner.kind() != ValueType::String {
-                        bail!(InterpreterError::UnsupportedValue(prop.clone()))
-                    }
-                    let prop_name = cast_value::<StringValue>(&prop_inner).unwrap().value();
-                    let obj_map = obj.map();
-                    if operator == AssignmentOperator::Equals {
-                        let right_val = self.resolve(right.clone(), env)?;
-                        obj.assign_property(prop_name, right_val);
-                        if let Node::Identifier(object_name) = *object {
-                            return Ok(self.assign_variable(
-                                object_name,
-                                Arc::new(Mutex::new(obj)),
-                                env,
-                                true,
-                            )?);
-                        } else {
-                            return Ok(self.eval_assignment_expression(
-                                object,
-                                operator,
-                                right.clone(),
-                                env,
-                            )?);
-                        }
-                    }
-                    let previous_value_opt = obj_map.get(&prop_name);
-                    if let Some(previous_value) = previous_value_opt {
-                        let left = self.convert_value_to_node(dyn_clone::clone_box(
-                            &**previous_value.lock().expect("assignment_expression: failed to get previous value for converting"),
-                        ));
-                        let binary_op = match operator {
-                            AssignmentOperator::Addition => BinaryOperator::Plus,
-                            AssignmentOperator::Division => BinaryOperator::Divide,
-                            AssignmentOperator::Modulation => BinaryOperator::Modulo,
-                            AssignmentOperator::Multiplication => BinaryOperator::Multiply,
-                            AssignmentOperator::Subtraction => BinaryOperator::Minus,
-                            AssignmentOperator::Equals => {
-                                panic!("unexpected equals assignment operator")
-                            }
+                        let previous_value = match class.get_static_property(prop_name.clone()) {
+                            Some(previous_value) => previous_value.value,
+                            None => bail!(InterpreterError::UnresolvedProperty(prop_name)),
                         };
-                        let binary = Node::BinaryExpression(left, binary_op, right.clone());
-                        let value = self.resolve(Box::new(binary), env)?;
-                        obj.assign_property(prop_name, value);
-                        if let Node::Identifier(object_name) = *object {
-                            Ok(self.assign_variable(
-                                object_name,
-                                Arc::new(Mutex::new(obj)),
-                                env,
-                                true,
-                            )?)
-                        } else {
-                            self.eval_assignment_expression(object, operator, right.clone(), env)
-                        }
-                    } else {
-                        bail!(InterpreterError::UnresolvedProperty(prop_name))
-                    }
+                        let binary_op = get_binary_operator(operator.clone());
+                        let right_value = self.resolve(right, env)?;
+                        self.eval_binary_values(previous_value, right_value, binary_op)?
+                    };
+                    // static properties are shared by every copy of the class
+                    class.assign_static_property(prop_name, value.clone());
+                    Ok(value)
                 }
-                ValueType::Class => {
-                    let mut class = cast_value::<ClassValue>(&obj_inner).unwrap();
-                    let prop: Arc<Mutex<Box<dyn RuntimeValue>>> = if computed {
-                        self.resolve(property, env)?
-                    } else {
-                        match *property {
-                            Node::Identifier(value) => {
-                                Arc::new(Mutex::new(Box::new(StringValue::from(value))))
-                            }
-                            _ => bail!(InterpreterError::UnsupportedNode(property)),
-                        }
+                Value::Array(array) if computed => {
+                    let index = match self.resolve(property, env)? {
+                        Value::Integer(index) => index,
+                        index => bail!(InterpreterError::UnsupportedValue(index)),
                     };
-                    let prop_inner = prop
-                        .lock()
-                        .expect("assignment_expression: failed to get property of class");
-                    if prop_inner.kind() != ValueType::String {
-                        bail!(InterpreterError::UnsupportedValue(prop.clone()))
-                    }
-                    let prop_name = cast_value::<StringValue>(&prop_inner).unwrap().value();
-                    if operator == AssignmentOperator::Equals {
-                        let right_val = self.resolve(right.clone(), env)?;
-                        class.insert_property(ClassProperty {
-                            is_static: true,
-                            name: prop_name,
-                            value: right_val,
-                        });
-                        if let Node::Identifier(object_name) = *object {
-                            return Ok(self.assign_variable(
-                                object_name,
-                                Arc::new(Mutex::new(class)),
-                                env,
-                                true,
-                            )?);
-                        } else {
-                            return Ok(self.eval_assignment_expression(
-                                object,
-                                operator,
-                                right.clone(),
-                                env,
-                            )?);
-                        }
-                    }
-                    let previous_value_opt = class.get_static_property(prop_name.clone());
-                    if let Some(previous_value) = previous_value_opt {
-                        let left = self.convert_value_to_node(dyn_clone::clone_box(
-                            &**previous_value.value.lock().expect(
-                                "assignment_expression: failed to get previous value of class for converting",
-                            ),
-                        ));
-                        let binary_op = match operator {
-                            AssignmentOperator::Addition => BinaryOperator::Plus,
-                            AssignmentOperator::Division => BinaryOperator::Divide,
-                            AssignmentOperator::Modulation => BinaryOperator::Modulo,
-                            AssignmentOperator::Multiplication => BinaryOperator::Multiply,
-                            AssignmentOperator::Subtraction => BinaryOperator::Minus,
-                            AssignmentOperator::Equals => {
-                                panic!("unexpected equals assignment operator")
-                            }
-                        };
-                        let binary = Node::BinaryExpression(left, binary_op, right.clone());
-                        let value = self.resolve(Box::new(binary), env)?;
-                        class.insert_property(ClassProperty {
-                            name: prop_name,
-                            is_static: true,
-                            value,
-                        });
-                        if let Node::Identifier(object_name) = *object {
-                            Ok(self.assign_variable(
-                                object_name,
-                                Arc::new(Mutex::new(class)),
-                                env,
-                                true,
-                            )?)
-                        } else {
-                            self.eval_assignment_expression(object, operator, right.clone(), env)
-                        }
+                    let index = resolve_index(index, array.len())?;
+                    let value = if *operator == AssignmentOperator::Equals {
+                        self.resolve(right, env)?
                     } else {
-                        bail!(InterpreterError::UnresolvedProperty(prop_name))
-                    }
+                        let previous_value = array.get_element(index).unwrap_or_default();
+                        let binary_op = get_binary_operator(operator.clone());
+                        let right = self.resolve(right, env)?;
+                        self.eval_binary_values(previous_value, right, binary_op)?
+                    };
+                    // the right side could have changed the length of the array
+                    let index = resolve_index(index as isize, array.len())?;
+                    array.set_element(index, value.clone());
+                    Ok(value)
                 }
-                _ => bail!(InterpreterError::InvalidValue(
-                    dyn_clone::clone_box(&**obj_inner),
-                    "object or class".to_string()
+                obj_val => bail!(InterpreterError::InvalidValue(
+                    obj_val,
+                    "object, class or array".to_string()
                 )),
             }
-        } else if let Node::Identifier(variable_name) = *left {
+        } else if let Node::Identifier(variable_name) = left {
             let value = match operator {
                 AssignmentOperator::Equals => {
                     let right = self.resolve(right, env)?;
-                    self.assign_variable(variable_name.clone(), right, env, false)?
+                    self.assign_variable(variable_name, right, env, false)?
                 }
                 assignment_operator => {
-                    let scope_state = SCOPE_STATE.lock().expect(
-                        "assignment_expression: failed to get scope state for non-equals operator",
-                    );
-                    let scope = scope_state.get_scope(env).unwrap();
-                    let previous_value =
-                        scope.lookup_variable(variable_name.clone(), &scope_state)?;
-                    let left = self.convert_value_to_node(dyn_clone::clone_box(
-                        &**previous_value.lock().expect(
-                            "assignment_expression: failed to get previous value for left operand",
-                        ),
-                    ));
-                    let operator = match assignment_operator {
-                        AssignmentOperator::Addition => BinaryOperator::Plus,
-                        AssignmentOperator::Division => BinaryOperator::Divide,
-                        AssignmentOperator::Modulation => BinaryOperator::Modulo,
-                        AssignmentOperator::Multiplication => BinaryOperator::Multiply,
-                        AssignmentOperator::Subtraction => BinaryOperator::Minus,
-                        AssignmentOperator::Equals => {
-                            panic!("unexpected equals assignment operator")
-                        }
-                    };
-                    let binary = Node::BinaryExpression(left, operator, right);
-                    let value = self.resolve(Box::new(binary), env)?;
-                    drop(scope_state);
-                    self.assign_variable(variable_name.clone(), value, env, false)?
+                    let previous_value = self.eval_identifier(variable_name, env)?;
+                    let operator = get_binary_operator(assignment_operator.clone());
+                    let right = self.resolve(right, env)?;
+                    let value = self.eval_binary_values(previous_value, right, operator)?;
+                    self.assign_variable(variable_name, value, env, false)?
                 }
             };
-            return Ok(value);
+            Ok(value)
         } else {
-            bail!(InterpreterError::InvalidAssignFactor(left))
+            bail!(InterpreterError::InvalidAssignFactor(Box::new(
+                left.clone()
+            )))
         }
     }
 
     fn eval_variable_declaration(
         &mut self,
-        variable_name: String,
-        value: Option<Box<Node>>,
+        variable_name: &str,
+        value: Option<&Node>,
         is_constant: bool,
         env: EnvironmentId,
-    ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError> {
-        let value: Arc<Mutex<Box<dyn RuntimeValue>>> = match value {
+    ) -> Result<Value, InterpreterError> {
+        let value = match value {
             Some(value) => self.resolve(value, env)?,
-            None => Arc::new(Mutex::new(Box::new(NullValue::default()))),
+            None => Value::Null,
         };
 
-        let scope_c = SCOPE_STATE.clone();
-        let mut scope_state = scope_c
-            .lock()
-            .expect("variable_declaration: failed to get scope state");
-        let scope = match scope_state.get_scope_mut(env) {
+        let scope = match self.scope_state.get_scope_mut(env) {
             Some(scope) => scope,
             None => bail!(InterpreterError::UnresolvedEnvironment(env)),
         };
 
-        let value = scope.declare_variable(variable_name.clone(), value, is_constant)?;
-        drop(scope_state);
+        let value = scope.declare_variable(variable_name.to_string(), value, is_constant)?;
 
         Ok(value)
     }
 
     fn eval_identifier(
         &mut self,
-        identifier: String,
+        identifier: &str,
         env: EnvironmentId,
-    ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError> {
-        let scope_state = SCOPE_STATE
-            .lock()
-            .expect("identifier: failed to get immutable scope state");
-        let scope = match scope_state.get_scope(env) {
+    ) -> Result<Value, InterpreterError> {
+        let scope = match self.scope_state.get_scope(env) {
             Some(scope) => scope,
             None => bail!(InterpreterError::UnresolvedEnvironment(env)),
         };
-        let val = scope.lookup_variable(identifier, &scope_state)?;
-        drop(scope_state);
-        Ok(val)
-    }
-
-    fn get_integer_value(
-        &mut self,
-        value: Box<dyn RuntimeValue>,
-    ) -> Result<Box<IntegerValue>, InterpreterError> {
-        let value_clone = dyn_clone::clone_box(&*value);
-        if value.kind() == ValueType::Integer {
-            let any = value.into_any();
-            match any.downcast::<IntegerValue>() {
-                Ok(value) => return Ok(value),
-                Err(_) => bail!(InterpreterError::ValueCastError(
-                    value_clone,
-                    "integer".to_string()
-                )),
-            }
-        }
-        bail!(InterpreterError::ValueCastError(
-            value,
-            "integer".to_string()
-        ))
+        scope.lookup_variable(identifier, &self.scope_state)
     }
 
-    fn get_decimal_value(
+    fn eval_binary_expression(
         &mut self,
-        value: Box<dyn RuntimeValue>,
-    ) -> Result<Box<DecimalValue>, InterpreterError> {
-        let value_clone = dyn_clone::clone_box(&*value);
-        if value.kind() == ValueType::Decimal {
-            let any = value.into_any();
-            match any.downcast::<DecimalValue>() {
-                Ok(value) => return Ok(value),
-                Err(_) => bail!(InterpreterError::ValueCastError(
-                    value_clone,
-                    "decimal".to_string()
-                )),
-            }
-        }
-        if value.kind() == ValueType::Integer {
-            let any = value.into_any();
-            match any.downcast::<IntegerValue>() {
-                Ok(value) => return Ok(Box::new(DecimalValue::from(value.value() as f64))),
-                Err(_) => bail!(InterpreterError::ValueCastError(
-                    value_clone,
-                    "decimal".to_string()
-                )),
-            }
+        node: &Node,
+        env: EnvironmentId,
+    ) -> Result<Value, InterpreterError> {
+        if let Node::BinaryExpression(left, operator, right_node) = node {
+            let left = self.resolve(left, env)?;
+            let right = self.resolve(right_node, env)?;
+            self.eval_binary_values(left, right, operator.clone())
+        } else {
+            bail!(InterpreterError::UnexpectedNode(Box::new(node.clone())))
         }
-        bail!(InterpreterError::ValueCastError(
-            value,
-            "decimal".to_string()
-        ))
     }
+}
 
-    fn eval_decimals<T>(
-        &mut self,
-        left: T,
-        right: T,
-        operator: BinaryOperator,
-    ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError>
-    where
-        T: Into<f64>,
-    {
-        let left: f64 = left.into();
-        let right: f64 = right.into();
-
-        match operator {
-            BinaryOperator::Plus => {
-                return Ok(Arc::new(Mutex::new(Box::new(DecimalValue::from(
-                    left + right,
-                )))))
-            }
-            BinaryOperator::Minus => {
-                return Ok(Arc::new(Mutex::new(Box::new(DecimalValue::from(
-                    left - right,
-                )))))
-            }
-            BinaryOperator::Divide => {
-                return Ok(Arc::new(Mutex::new(Box::new(DecimalValue::from(
-                    left / right,
-                )))))
-            }
-            BinaryOperator::Multiply => {
-                return Ok(Arc::new(Mutex::new(Box::new(DecimalValue::from(
-                    left * right,
-                )))))
-            }
-            BinaryOperator::Modulo => {
-                return Ok(Arc::new(Mutex::new(Box::new(DecimalValue::from(
-                    left % right,
-                )))))
-            }
-            BinaryOperator::GreaterThan => {
-                return Ok(Arc::new(Mutex::new(Box::new(BoolValue::from(
-                    left > right,
-                )))))
-            }
-            BinaryOperator::LessThan => {
-                return Ok(Arc::new(Mutex::new(Box::new(BoolValue::from(
-                    left < right,
-                )))))
-            }
-            BinaryOperator::NotEquals => {
-                return Ok(Arc::new(Mutex::new(Box::new(BoolValue::from(
-                    left != right,
-                )))))
-            }
-            BinaryOperator::IsEquals => {
-                return Ok(Arc::new(Mutex::new(Box::new(BoolValue::from(
-                    left == right,
-                )))))
-            }
-        }
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    fn eval_integers<T>(
-        &mut self,
-        left: T,
-        right: T,
-        operator: BinaryOperator,
-    ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError>
-    where
-        T: Into<isize> + Debug,
-    {
-        let left: isize = left.into();
-        let right: isize = right.into();
-
-        match operator {
-            BinaryOperator::Plus => {
-                return Ok(Arc::new(Mutex::new(Box::new(IntegerValue::from(
-                    left + right,
-                )))))
-            }
-            BinaryOperator::Minus => {
-                return Ok(Arc::new(Mutex::new(Box::new(IntegerValue::from(
-                    left - right,
-                )))))
-            }
-            BinaryOperator::Divide => {
-                return Ok(Arc::new(Mutex::new(Box::new(IntegerValue::from(
-                    left / right,
-                )))))
-            }
-            BinaryOperator::Multiply => {
-                return Ok(Arc::new(Mutex::new(Box::new(IntegerValue::from(
-                    left * right,
-                )))))
-            }
-            BinaryOperator::Modulo => {
-                return Ok(Arc::new(Mutex::new(Box::new(IntegerValue::from(
-                    left % right,
-                )))))
-            }
-            BinaryOperator::GreaterThan => {
-                return Ok(Arc::new(Mutex::new(Box::new(BoolValue::from(
-                    left > right,
-                )))))
-            }
-            BinaryOperator::LessThan => {
-                return Ok(Arc::new(Mutex::new(Box::new(BoolValue::from(
-                    left < right,
-                )))))
-            }
-            BinaryOperator::NotEquals => {
-                return Ok(Arc::new(Mutex::new(Box::new(BoolValue::from(
-                    left != right,
-                )))))
-            }
-            BinaryOperator::IsEquals => {
-                return Ok(Arc::new(Mutex::new(Box::new(BoolValue::from(
-                    left == right,
-                )))));
-            }
-        }
+impl Engine for Interpreter {
+    fn scope_state_mut(&mut self) -> &mut ScopeState {
+        &mut self.scope_state
     }
 
-    fn eval_numeric_binary_expression(
-        &mut self,
-        left: Arc<Mutex<Box<dyn RuntimeValue>>>,
-        right: Arc<Mutex<Box<dyn RuntimeValue>>>,
-        operator: BinaryOperator,
-    ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError> {
-        let left = left
-            .lock()
-            .expect("numeric_binary_expression: failed to get left");
-        let right = right
-            .lock()
-            .expect("numeric_binary_expression: failed to get right");
-        if left.kind() == ValueType::Decimal || right.kind() == ValueType::Decimal {
-            // we are trying to add 1-2 decimals -> will get decimal
-            let left = self.get_decimal_value(dyn_clone::clone_box(&**left))?;
-            let right = self.get_decimal_value(dyn_clone::clone_box(&**right))?;
-
-            return Ok(self.eval_decimals(left.value(), right.value(), operator)?);
-        }
-        if left.kind() == ValueType::Integer && right.kind() == ValueType::Integer {
-            let left = self.get_integer_value(dyn_clone::clone_box(&**left))?;
-            let right = self.get_integer_value(dyn_clone::clone_box(&**right))?;
-
-            return Ok(self.eval_integers(left.value(), right.value(), operator)?);
-        }
-
-        if left.kind() != ValueType::Integer {
-            bail!(InterpreterError::UnsupportedValue(Arc::new(Mutex::new(
-                dyn_clone::clone_box(&**left)
-            ))))
-        } else {
-            bail!(InterpreterError::UnsupportedValue(Arc::new(Mutex::new(
-                dyn_clone::clone_box(&**right)
-            ))))
-        }
+    fn modules_mut(&mut self) -> &mut Modules {
+        &mut self.modules
     }
 
-    fn eval_string_binary_expression(
+    fn call_class_method(
         &mut self,
-        left: Arc<Mutex<Box<dyn RuntimeValue>>>,
-        right: Arc<Mutex<Box<dyn RuntimeValue>>>,
-        operator: BinaryOperator,
-    ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError> {
-        let left = left
-            .lock()
-            .expect("string_binary_expression: failed to get left");
-        let right = right
-            .lock()
-            .expect("string_binary_expression: failed to get right");
-
-        let left_str = cast_value::<StringValue>(&left).unwrap();
-        let right_str = cast_value::<StringValue>(&right).unwrap();
-
-        match operator {
-            BinaryOperator::IsEquals => {
-                return Ok(Arc::new(Mutex::new(Box::new(BoolValue::from(
-                    left_str.value() == right_str.value(),
-                )))))
-            }
-            // TODO: maybe adding string concatination as Plus?
-            op => bail!(InterpreterError::UnsupportedBinaryOperator(op)),
+        class: &Arc<ClassValue>,
+        receiver: Value,
+        name: &str,
+        args: Vec<Value>,
+    ) -> Result<Option<Value>, InterpreterError> {
+        match class.get_method(name) {
+            Some(method) => Ok(Some(self.eval_class_call(receiver, &method, args)?)),
+            None => Ok(None),
         }
     }
 
-    fn eval_binary_expression(
-        &mut self,
-        node: Box<Node>,
-        env: EnvironmentId,
-    ) -> Result<Arc<Mutex<Box<dyn RuntimeValue>>>, InterpreterError> {
-        if let Node::BinaryExpression(left, operator, right_node) = *node {
-            let left = self.resolve(left, env)?;
-            let right = self.resolve(right_node, env)?;
-
-            let left_inn = left.lock().expect("binary_expression: failed to get left");
-            let left_kind = dyn_clone::clone_box(&**left_inn).kind();
-            let right_inn = right
-                .lock()
-                .expect("binary_expression: failed to get right");
-            let right_kind = dyn_clone::clone_box(&**right_inn).kind();
-            drop(left_inn);
-            drop(right_inn);
-
-            if (left_kind == ValueType::Integer || left_kind == ValueType::Decimal)
-                && (right_kind == ValueType::Integer || right_kind == ValueType::Decimal)
-            {
-                let value = self.eval_numeric_binary_expression(left, right, operator)?;
-                return Ok(value);
-            } else if left_kind == ValueType::String && right_kind == ValueType::String {
-                return Ok(self.eval_string_binary_expression(left, right, operator)?);
-            }
-
-            return Ok(Arc::new(Mutex::new(Box::new(NullValue::default()))));
-        } else {
-            bail!(InterpreterError::UnexpectedNode(node))
-        }
+    fn run_module(&mut self, ast: Node, env: EnvironmentHandle) -> Result<(), InterpreterError> {
+        // the module gets its own statement queue, the importing file continues afterwards
+        let stack = std::mem::take(&mut self.stack);
+        let result = self.run(Box::new(ast), env.id());
+        self.stack = stack;
+        result
     }
 }
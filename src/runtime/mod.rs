@@ -1,5 +1,13 @@
+mod engine;
 mod error;
 mod interpreter;
+mod module;
+mod operators;
+mod resolver;
 
+pub use engine::*;
 pub use error::*;
 pub use interpreter::*;
+pub use module::*;
+pub use operators::*;
+pub use resolver::*;
@@ -0,0 +1,113 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use pl_ast::{
+    token::{Token, TokenKind},
+    Lexer, Node, Parser,
+};
+
+use crate::{macros::bail, EnvironmentHandle, ScopeState, Value};
+
+use super::InterpreterError;
+
+/// Script file evaluated into its own environment, cached by its canonical path
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub path: PathBuf,
+    pub env: EnvironmentHandle,
+    /// Names of the top level declarations marked with `export`
+    pub exports: Vec<String>,
+}
+
+impl Module {
+    pub fn new(path: PathBuf, env: EnvironmentHandle) -> Self {
+        Self {
+            path,
+            env,
+            exports: vec![],
+        }
+    }
+
+    /// Current value of an exported declaration, `path` is the import path for the error
+    pub fn get_export(
+        &self,
+        scope_state: &ScopeState,
+        path: &str,
+        name: &str,
+    ) -> Result<Value, InterpreterError> {
+        let value = match self.exports.iter().any(|export| export == name) {
+            true => scope_state
+                .get_scope(self.env.id())
+                .and_then(|scope| scope.variables.get(name)),
+            false => None,
+        };
+        match value {
+            Some(value) => Ok(value.clone()),
+            None => bail!(InterpreterError::UnresolvedExport(
+                path.to_string(),
+                name.to_string()
+            )),
+        }
+    }
+}
+
+/// Modules of an engine, each file is evaluated once no matter how often it is imported
+#[derive(Debug, Default)]
+pub struct Modules {
+    /// Directories to look up imports in, unless the import is relative to the importing file
+    pub search_paths: Vec<PathBuf>,
+    pub loaded: HashMap<PathBuf, Module>,
+    /// Modules that are being evaluated, the last one is the current module
+    pub stack: Vec<Module>,
+}
+
+pub fn parse_source(source: String) -> Result<Node, String> {
+    let mut lexer = Lexer::new(source);
+    lexer.tokenize().map_err(|err| err.to_string())?;
+    let mut parser = Parser::new(
+        lexer
+            .tokens
+            .iter()
+            .filter(|token| token.kind() != TokenKind::Newline)
+            .map(|token| dyn_clone::clone_box(&**token))
+            .collect::<Vec<Box<dyn Token>>>(),
+    );
+    parser.produce_ast().map_err(|err| err.to_string())
+}
+
+/// `./` and `../` paths are relative to the importing module, others are looked up in the search paths
+pub fn resolve_module_path(
+    path: &str,
+    importer: Option<&Module>,
+    search_paths: &[PathBuf],
+) -> Result<PathBuf, InterpreterError> {
+    let candidates: Vec<PathBuf> = if path.starts_with("./") || path.starts_with("../") {
+        let base = match importer {
+            Some(module) => module.path.parent().unwrap_or(Path::new("")).to_path_buf(),
+            None => PathBuf::new(),
+        };
+        vec![base.join(path)]
+    } else if Path::new(path).is_absolute() {
+        vec![PathBuf::from(path)]
+    } else {
+        search_paths.iter().map(|dir| dir.join(path)).collect()
+    };
+
+    for candidate in candidates {
+        // the extension can be omitted, e.g. `import "lib"`
+        let candidate = if candidate.extension().is_none() {
+            candidate.with_extension("amr")
+        } else {
+            candidate
+        };
+        if let Ok(path) = candidate.canonicalize() {
+            if path.is_file() {
+                return Ok(path);
+            }
+        }
+    }
+
+    bail!(InterpreterError::ModuleNotFound(path.to_string()))
+}
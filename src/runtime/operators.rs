@@ -0,0 +1,230 @@
+use std::sync::Arc;
+
+use pl_ast::{AssignmentOperator, BinaryOperator};
+
+use crate::{macros::bail, values::ValueType, Key, Value};
+
+use super::InterpreterError;
+
+/// Binary operation on plain values, operator methods of instances are handled by the engines
+pub fn eval_binary_values(
+    left: Value,
+    right: Value,
+    operator: BinaryOperator,
+) -> Result<Value, InterpreterError> {
+    // integers only stay integers with each other, anything else with a decimal is a decimal
+    match (&left, &right) {
+        (Value::Integer(left), Value::Integer(right)) => eval_integers(*left, *right, operator),
+        (Value::Decimal(left), Value::Decimal(right)) => eval_decimals(*left, *right, operator),
+        (Value::Decimal(left), Value::Integer(right)) => {
+            eval_decimals(*left, *right as f64, operator)
+        }
+        (Value::Integer(left), Value::Decimal(right)) => {
+            eval_decimals(*left as f64, *right, operator)
+        }
+        (Value::String(left), Value::String(right)) => eval_strings(left, right, operator),
+        _ => eval_equality(left, right, operator),
+    }
+}
+
+fn eval_decimals(
+    left: f64,
+    right: f64,
+    operator: BinaryOperator,
+) -> Result<Value, InterpreterError> {
+    let error = || {
+        InterpreterError::ArithmeticError(operator.clone(), Value::from(left), Value::from(right))
+    };
+    // finite operands must produce a finite result, anything else is an overflow
+    let checked = |result: f64| {
+        if result.is_finite() || !left.is_finite() || !right.is_finite() {
+            Ok(result)
+        } else {
+            Err(error())
+        }
+    };
+
+    let result = match operator {
+        BinaryOperator::Plus => Value::from(checked(left + right)?),
+        BinaryOperator::Minus => Value::from(checked(left - right)?),
+        BinaryOperator::Multiply => Value::from(checked(left * right)?),
+        BinaryOperator::Divide | BinaryOperator::Modulo if right == 0.0 => {
+            bail!(error())
+        }
+        BinaryOperator::Divide => Value::from(checked(left / right)?),
+        BinaryOperator::Modulo => Value::from(checked(left % right)?),
+        BinaryOperator::GreaterThan => Value::from(left > right),
+        BinaryOperator::LessThan => Value::from(left < right),
+        BinaryOperator::NotEquals => Value::from(left != right),
+        BinaryOperator::IsEquals => Value::from(left == right),
+    };
+    Ok(result)
+}
+
+fn eval_integers(
+    left: isize,
+    right: isize,
+    operator: BinaryOperator,
+) -> Result<Value, InterpreterError> {
+    // checked operations return None on overflow and division by zero
+    let checked = |result: Option<isize>| {
+        result.ok_or_else(|| {
+            InterpreterError::ArithmeticError(
+                operator.clone(),
+                Value::from(left),
+                Value::from(right),
+            )
+        })
+    };
+
+    let result = match operator {
+        BinaryOperator::Plus => Value::from(checked(left.checked_add(right))?),
+        BinaryOperator::Minus => Value::from(checked(left.checked_sub(right))?),
+        BinaryOperator::Divide => Value::from(checked(left.checked_div(right))?),
+        BinaryOperator::Multiply => Value::from(checked(left.checked_mul(right))?),
+        BinaryOperator::Modulo => Value::from(checked(left.checked_rem(right))?),
+        BinaryOperator::GreaterThan => Value::from(left > right),
+        BinaryOperator::LessThan => Value::from(left < right),
+        BinaryOperator::NotEquals => Value::from(left != right),
+        BinaryOperator::IsEquals => Value::from(left == right),
+    };
+    Ok(result)
+}
+
+fn eval_strings(
+    left: &str,
+    right: &str,
+    operator: BinaryOperator,
+) -> Result<Value, InterpreterError> {
+    let result = match operator {
+        BinaryOperator::Plus => Value::from(format!("{}{}", left, right)),
+        BinaryOperator::IsEquals => Value::from(left == right),
+        BinaryOperator::NotEquals => Value::from(left != right),
+        BinaryOperator::LessThan => Value::from(left < right),
+        BinaryOperator::GreaterThan => Value::from(left > right),
+        op => bail!(InterpreterError::UnsupportedOperands(
+            op,
+            ValueType::String,
+            ValueType::String
+        )),
+    };
+    Ok(result)
+}
+
+fn eval_equality(
+    left: Value,
+    right: Value,
+    operator: BinaryOperator,
+) -> Result<Value, InterpreterError> {
+    // values of different kinds are never equal,
+    // arrays, objects and instances are compared by reference
+    let is_equal = match (&left, &right) {
+        (Value::Null, Value::Null) => true,
+        (Value::Boolean(left), Value::Boolean(right)) => left == right,
+        (Value::Array(left), Value::Array(right)) => left.is_same(right),
+        (Value::Object(left), Value::Object(right)) => left.is_same(right),
+        (Value::ClassInstance(left), Value::ClassInstance(right)) => left.is_same(right),
+        (Value::Class(left), Value::Class(right)) => left.is_same(right),
+        // the same declaration evaluated in the same environment
+        (Value::Function(left), Value::Function(right)) => {
+            Arc::ptr_eq(&left.body, &right.body)
+                && left.declaration_env.id() == right.declaration_env.id()
+        }
+        (Value::Closure(left), Value::Closure(right)) => left.is_same(right),
+        (Value::NativeFn(left), Value::NativeFn(right)) => left.name == right.name,
+        // numbers and strings have their own binary expressions
+        _ => false,
+    };
+
+    match operator {
+        BinaryOperator::IsEquals => Ok(Value::from(is_equal)),
+        BinaryOperator::NotEquals => Ok(Value::from(!is_equal)),
+        op => bail!(InterpreterError::UnsupportedOperands(
+            op,
+            left.kind(),
+            right.kind()
+        )),
+    }
+}
+
+/// Name of the method that overloads the operator, e.g. `a + b` calls `a.__add__(b)`
+pub fn operator_method_name(operator: &BinaryOperator) -> &'static str {
+    match operator {
+        BinaryOperator::Plus => "__add__",
+        BinaryOperator::Minus => "__sub__",
+        BinaryOperator::Multiply => "__mul__",
+        BinaryOperator::Divide => "__div__",
+        BinaryOperator::Modulo => "__mod__",
+        BinaryOperator::LessThan => "__lt__",
+        BinaryOperator::GreaterThan => "__gt__",
+        BinaryOperator::IsEquals | BinaryOperator::NotEquals => "__eq__",
+    }
+}
+
+/// Binary operator of a compound assignment, e.g. `+=` is `+`
+pub fn get_binary_operator(operator: AssignmentOperator) -> BinaryOperator {
+    match operator {
+        AssignmentOperator::Addition => BinaryOperator::Plus,
+        AssignmentOperator::Division => BinaryOperator::Divide,
+        AssignmentOperator::Modulation => BinaryOperator::Modulo,
+        AssignmentOperator::Multiplication => BinaryOperator::Multiply,
+        AssignmentOperator::Subtraction => BinaryOperator::Minus,
+        AssignmentOperator::Equals => {
+            panic!("unexpected equals assignment operator")
+        }
+    }
+}
+
+/// Negative indices count from the end
+pub fn resolve_index(index: isize, length: usize) -> Result<usize, InterpreterError> {
+    let resolved = if index < 0 {
+        index + length as isize
+    } else {
+        index
+    };
+    if resolved < 0 || resolved as usize >= length {
+        bail!(InterpreterError::IndexOutOfRange(index, length))
+    }
+    Ok(resolved as usize)
+}
+
+/// Element of an array or a character of a string, negative indices count from the end
+pub fn get_element(object: Value, index: isize) -> Result<Value, InterpreterError> {
+    match object {
+        Value::Array(array) => {
+            let index = resolve_index(index, array.len())?;
+            Ok(array.get_element(index).unwrap_or_default())
+        }
+        Value::String(string) => {
+            let chars = string.chars().collect::<Vec<char>>();
+            let index = resolve_index(index, chars.len())?;
+            Ok(Value::from(chars[index].to_string()))
+        }
+        object => bail!(InterpreterError::InvalidValue(
+            object,
+            "array or string".to_string()
+        )),
+    }
+}
+
+/// Items a for-in loop goes through, the keys of an object are sorted
+pub fn get_items(iterable: Value) -> Result<Vec<Value>, InterpreterError> {
+    let items = match iterable {
+        Value::Array(array) => array.value(),
+        Value::String(string) => string
+            .chars()
+            .map(|char| Value::from(char.to_string()))
+            .collect(),
+        Value::Object(object) => {
+            let mut keys = object.map().into_keys().collect::<Vec<Key>>();
+            // hash map order is random, keep iteration predictable
+            keys.sort();
+            keys.into_iter().map(Value::from).collect()
+        }
+        iterable => bail!(InterpreterError::InvalidValue(
+            iterable,
+            "array, string or object".to_string()
+        )),
+    };
+    Ok(items)
+}
@@ -0,0 +1,326 @@
+use std::collections::HashSet;
+
+use pl_ast::Node;
+
+use crate::{macros::bail, EnvironmentId, Prototypes, ScopeState};
+
+use super::InterpreterError;
+
+/// Names declared in one environment
+#[derive(Default)]
+struct Scope {
+    names: HashSet<String>,
+    /// `import "path"` declares names that aren't known until the module is loaded
+    is_open: bool,
+}
+
+enum Lookup {
+    Found,
+    /// Only known once the code runs
+    Dynamic,
+    Missing,
+}
+
+/// Static pass over the program that finds the declaration of every identifier,
+/// so unresolved names are reported before anything runs.
+/// The scopes mirror the environments `Interpreter` creates for blocks, calls, loops and catch clauses.
+/// Nothing of the resolution is kept, `Interpreter` still looks variables up by name
+/// and only the compiler of `Vm` turns locals into slots
+pub struct Resolver {
+    scopes: Vec<Scope>,
+}
+
+impl Resolver {
+    /// Starts with the names the environment chain already has, e.g. the builtins
+    pub fn new(scope_state: &ScopeState, env: EnvironmentId) -> Self {
+        let mut scopes = vec![];
+        let mut current = scope_state.get_scope(env);
+        while let Some(scope) = current {
+            scopes.push(Scope {
+                names: scope.variables.keys().cloned().collect(),
+                is_open: false,
+            });
+            current = scope
+                .parent
+                .and_then(|parent| scope_state.get_scope(parent));
+        }
+        scopes.reverse();
+        if scopes.is_empty() {
+            scopes.push(Scope::default());
+        }
+        Self { scopes }
+    }
+
+    /// Reports the first name that isn't declared anywhere the program can see,
+    /// the program is evaluated in the environment of the resolver
+    pub fn check_program(mut self, program: &Node) -> Result<(), InterpreterError> {
+        // top level statements run in the environment itself, even the ones of a block
+        let statements: Vec<&Node> = match program {
+            Node::Program(statements) | Node::BlockStatement(statements) => {
+                statements.iter().map(|statement| &**statement).collect()
+            }
+            node => vec![node],
+        };
+        let scope = self.scopes.last_mut().unwrap();
+        for statement in &statements {
+            collect_declarations(statement, scope);
+        }
+        for statement in statements {
+            self.visit(statement)?;
+        }
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Lookup {
+        for scope in self.scopes.iter().rev() {
+            if scope.names.contains(name) {
+                return Lookup::Found;
+            }
+            if scope.is_open {
+                return Lookup::Dynamic;
+            }
+        }
+        Lookup::Missing
+    }
+
+    fn visit_identifier(&self, name: &str) -> Result<(), InterpreterError> {
+        // declared by method calls, which plain functions can become at any time
+        if name == "self" || name == "__class__" {
+            return Ok(());
+        }
+        match self.lookup(name) {
+            Lookup::Found | Lookup::Dynamic => Ok(()),
+            Lookup::Missing => bail!(InterpreterError::UnresolvedVariable(name.to_string())),
+        }
+    }
+
+    fn visit_scope(&mut self, scope: Scope, nodes: &[&Node]) -> Result<(), InterpreterError> {
+        self.scopes.push(scope);
+        let result = nodes.iter().try_for_each(|node| self.visit(node));
+        self.scopes.pop();
+        result
+    }
+
+    fn visit_block(&mut self, statements: &[Box<Node>]) -> Result<(), InterpreterError> {
+        let mut scope = Scope::default();
+        for statement in statements {
+            collect_declarations(statement, &mut scope);
+        }
+        let statements = statements
+            .iter()
+            .map(|statement| &**statement)
+            .collect::<Vec<&Node>>();
+        self.visit_scope(scope, &statements)
+    }
+
+    /// Parameters are declared in the call environment, the body gets its own block
+    fn visit_function(
+        &mut self,
+        parameters: &[Box<Node>],
+        body: &Node,
+    ) -> Result<(), InterpreterError> {
+        let mut frame = Scope::default();
+        let mut nodes = vec![];
+        for parameter in parameters {
+            match &**parameter {
+                Node::Identifier(name) | Node::RestElement(name) => {
+                    frame.names.insert(name.clone());
+                }
+                Node::AssignmentExpression(left, _, default_value) => {
+                    if let Node::Identifier(name) = &**left {
+                        frame.names.insert(name.clone());
+                    }
+                    nodes.push(&**default_value);
+                }
+                _ => {}
+            }
+        }
+        // default values are evaluated in the call environment, next to the parameters
+        nodes.push(body);
+        self.visit_scope(frame, &nodes)
+    }
+
+    fn visit(&mut self, node: &Node) -> Result<(), InterpreterError> {
+        match node {
+            Node::Program(statements) | Node::BlockStatement(statements) => {
+                self.visit_block(statements)?
+            }
+            Node::Identifier(name) => self.visit_identifier(name)?,
+            Node::ArrayExpression(items) => {
+                for item in items {
+                    self.visit(item)?;
+                }
+            }
+            Node::ObjectExpression(properties) => {
+                for property in properties {
+                    if let Node::Property(_, value) = &**property {
+                        self.visit(value)?;
+                    }
+                }
+            }
+            Node::BinaryExpression(left, _, right)
+            | Node::LogicalExpression(left, _, right)
+            | Node::AssignmentExpression(left, _, right) => {
+                self.visit(left)?;
+                self.visit(right)?;
+            }
+            Node::UnaryExpression(value, _)
+            | Node::ReturnStatement(value)
+            | Node::ThrowStatement(value)
+            | Node::ExportDeclaration(value) => self.visit(value)?,
+            Node::VariableDeclaration(_, Some(value), _) => self.visit(value)?,
+            Node::MemberExpression(object, property, computed) => {
+                self.visit(object)?;
+                if *computed {
+                    self.visit(property)?;
+                }
+            }
+            Node::CallExpression(callee, args) => {
+                for arg in args {
+                    self.visit(arg)?;
+                }
+                // `super(...)` and `super.method(...)` aren't variables
+                match &**callee {
+                    Node::Identifier(name) if name == "super" => {}
+                    Node::MemberExpression(object, _, false) if matches!(&**object, Node::Identifier(name) if name == "super") =>
+                        {}
+                    callee => self.visit(callee)?,
+                }
+            }
+            Node::FunctionDeclaration(_, parameters, body)
+            | Node::FunctionExpression(parameters, body) => {
+                self.visit_function(parameters, body)?
+            }
+            Node::ClassDeclaration(_, super_class, body) => {
+                if let Some(super_class) = super_class {
+                    self.visit(super_class)?;
+                }
+                for member in body {
+                    match &**member {
+                        Node::PropertyDefinition(_, value, _) => self.visit(value)?,
+                        Node::MethodDefinition(_, parameters, body, _) => {
+                            self.visit_function(parameters, body)?
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Node::IfStatement(condition, body, alternate) => {
+                self.visit(condition)?;
+                self.visit(body)?;
+                if let Some(alternate) = alternate {
+                    self.visit(alternate)?;
+                }
+            }
+            Node::WhileStatement(condition, body) => {
+                self.visit(condition)?;
+                self.visit(body)?;
+            }
+            Node::ForInStatement(left, iterable, body) => {
+                self.visit(iterable)?;
+                // every iteration declares the loop variable in its own environment
+                let mut scope = Scope::default();
+                if let Node::Identifier(name) = &**left {
+                    scope.names.insert(name.clone());
+                }
+                self.visit_scope(scope, &[&**body])?;
+            }
+            Node::TryStatement(block, handler, finalizer) => {
+                self.visit(block)?;
+                if let Some(handler) = handler {
+                    if let Node::CatchClause(parameter, body) = &**handler {
+                        let mut scope = Scope::default();
+                        scope.names.extend(parameter.clone());
+                        self.visit_scope(scope, &[&**body])?;
+                    }
+                }
+                if let Some(finalizer) = finalizer {
+                    self.visit(finalizer)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Adds the names the node declares in the environment it is evaluated in,
+/// blocks, functions, loop bodies and catch clauses get environments of their own
+fn collect_declarations(node: &Node, scope: &mut Scope) {
+    match node {
+        Node::VariableDeclaration(name, value, _) => {
+            scope.names.insert(name.clone());
+            if let Some(value) = value {
+                collect_declarations(value, scope);
+            }
+        }
+        Node::FunctionDeclaration(name, ..) => {
+            scope.names.insert(name.clone());
+        }
+        Node::ClassDeclaration(name, super_class, body) => {
+            // classes named after a builtin type extend it, see `Prototypes`
+            if !Prototypes::is_extendable(name) {
+                scope.names.insert(name.clone());
+            }
+            if let Some(super_class) = super_class {
+                collect_declarations(super_class, scope);
+            }
+            for member in body {
+                if let Node::PropertyDefinition(_, value, _) = &**member {
+                    collect_declarations(value, scope);
+                }
+            }
+        }
+        Node::ImportDeclaration(names, _) => {
+            if names.is_empty() {
+                scope.is_open = true;
+            }
+            scope.names.extend(names.iter().cloned());
+        }
+        Node::ExportDeclaration(declaration) => collect_declarations(declaration, scope),
+        Node::ArrayExpression(items) => {
+            for item in items {
+                collect_declarations(item, scope);
+            }
+        }
+        Node::CallExpression(callee, args) => {
+            collect_declarations(callee, scope);
+            for arg in args {
+                collect_declarations(arg, scope);
+            }
+        }
+        Node::ObjectExpression(properties) => {
+            for property in properties {
+                if let Node::Property(_, value) = &**property {
+                    collect_declarations(value, scope);
+                }
+            }
+        }
+        Node::BinaryExpression(left, _, right)
+        | Node::LogicalExpression(left, _, right)
+        | Node::AssignmentExpression(left, _, right)
+        | Node::MemberExpression(left, right, _)
+        | Node::WhileStatement(left, right) => {
+            collect_declarations(left, scope);
+            collect_declarations(right, scope);
+        }
+        Node::UnaryExpression(value, _)
+        | Node::ReturnStatement(value)
+        | Node::ThrowStatement(value)
+        | Node::ForInStatement(_, value, _) => collect_declarations(value, scope),
+        Node::IfStatement(condition, body, alternate) => {
+            collect_declarations(condition, scope);
+            collect_declarations(body, scope);
+            if let Some(alternate) = alternate {
+                collect_declarations(alternate, scope);
+            }
+        }
+        Node::TryStatement(block, _, finalizer) => {
+            collect_declarations(block, scope);
+            if let Some(finalizer) = finalizer {
+                collect_declarations(finalizer, scope);
+            }
+        }
+        _ => {}
+    }
+}
@@ -2,64 +2,87 @@ use std::sync::{Arc, Mutex};
 
 use serde::Serialize;
 
-use super::{RuntimeValue, ValueType};
+use crate::Tracer;
 
-#[derive(Debug, Serialize)]
+use super::Value;
+
+/// Arrays are shared by reference, clones point to the same items
+#[derive(Debug, Clone, Serialize)]
 pub struct ArrayValue {
-    kind: ValueType,
-    value: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>,
+    value: Arc<Mutex<Vec<Value>>>,
 }
 
-impl Clone for ArrayValue {
-    fn clone(&self) -> Self {
-        let mut cloned_value: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>> = vec![];
-        for value in self.value.as_slice() {
-            cloned_value.push(Arc::new(Mutex::new(dyn_clone::clone_box(
-                &**value
-                    .lock()
-                    .expect("array_clone: failed to get value of slice"),
-            ))));
-        }
+impl From<Vec<Value>> for ArrayValue {
+    fn from(value: Vec<Value>) -> Self {
         Self {
-            kind: self.kind.clone(),
-            value: cloned_value,
+            value: Arc::new(Mutex::new(value)),
         }
     }
 }
 
-impl RuntimeValue for ArrayValue {
-    fn kind(&self) -> ValueType {
-        self.kind
+impl ArrayValue {
+    pub fn value(&self) -> Vec<Value> {
+        self.value
+            .lock()
+            .expect("array.value(): failed to get items")
+            .clone()
     }
 
-    fn into_any(&self) -> Box<dyn std::any::Any> {
-        Box::new(dyn_clone::clone(self))
+    pub fn len(&self) -> usize {
+        self.value
+            .lock()
+            .expect("array.len(): failed to get items")
+            .len()
     }
-}
 
-impl From<Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>> for ArrayValue {
-    fn from(value: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>) -> Self {
-        Self {
-            kind: ValueType::Array,
-            value,
-        }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
-}
 
-impl ArrayValue {
-    pub fn value(&self) -> Vec<Arc<Mutex<Box<dyn RuntimeValue>>>> {
-        let mut cloned_value: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>> = vec![];
-        for value in self.value.as_slice() {
-            cloned_value.push(Arc::new(Mutex::new(dyn_clone::clone_box(
-                &**value
-                    .lock()
-                    .expect("array.value(): failed to get value of slice"),
-            ))));
-        }
-        cloned_value
+    pub fn get_element(&self, index: usize) -> Option<Value> {
+        self.value
+            .lock()
+            .expect("array.get_element(): failed to get items")
+            .get(index)
+            .cloned()
+    }
+
+    /// Whether both values point to the same array
+    pub fn is_same(&self, other: &ArrayValue) -> bool {
+        Arc::ptr_eq(&self.value, &other.value)
+    }
+
+    /// Address of the items, the same for every value that points to the array
+    pub fn id(&self) -> usize {
+        Arc::as_ptr(&self.value) as usize
+    }
+
+    pub fn append_element(&self, element: Value) {
+        self.value
+            .lock()
+            .expect("array.append_element(): failed to get items")
+            .push(element)
+    }
+
+    /// Replaces all of the items, every value that points to the array sees the new ones
+    pub fn replace_elements(&self, elements: Vec<Value>) {
+        *self
+            .value
+            .lock()
+            .expect("array.replace_elements(): failed to get items") = elements
+    }
+
+    pub fn set_element(&self, index: usize, element: Value) {
+        self.value
+            .lock()
+            .expect("array.set_element(): failed to get items")[index] = element
     }
 
-    pub fn append_element(&mut self, element: Arc<Mutex<Box<dyn RuntimeValue>>>) {
-        self.value.push(element)
+    pub fn trace(&self, tracer: &mut Tracer) {
+        tracer.trace_locked(&self.value, |items, tracer| {
+            for item in items {
+                tracer.trace_value(item);
+            }
+        });
     }
 }
@@ -1,96 +1,85 @@
-use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
-};
+use std::{collections::HashMap, sync::Arc};
 
 use pl_ast::Node;
 use serde::Serialize;
 
-use super::{RuntimeValue, ValueType};
+use crate::{EnvironmentHandle, Tracer};
 
-#[derive(Debug, Serialize)]
+use super::{ClosureValue, FunctionParameter, FunctionValue, ObjectValue, Value};
+
+#[derive(Debug, Clone, Serialize)]
 pub struct ClassProperty {
     pub name: String,
-    pub value: Arc<Mutex<Box<dyn RuntimeValue>>>,
+    pub value: Value,
     pub is_static: bool,
 }
 
-impl Clone for ClassProperty {
-    fn clone(&self) -> Self {
-        let value = self.value.lock().expect("class_clone: failed to get value");
-        Self {
-            name: self.name.clone(),
-            value: Arc::new(Mutex::new(dyn_clone::clone_box(&**value))),
-            is_static: self.is_static,
-        }
-    }
+#[derive(Clone, Debug, Serialize)]
+pub struct ClassMethodParameter {
+    pub name: String,
+    /// Evaluated at call time when the argument is omitted
+    pub default_value: Option<Box<Node>>,
+    pub is_rest: bool,
 }
 
-#[derive(Debug, Serialize)]
-pub struct ClassMethodParameter {
+#[derive(Clone, Debug, Serialize)]
+pub struct ClassMethod {
     pub name: String,
-    pub default_value: Option<Arc<Mutex<Box<dyn RuntimeValue>>>>,
+    pub args: Vec<ClassMethodParameter>,
+    pub body: Arc<Node>,
+    pub is_static: bool,
 }
 
-impl Clone for ClassMethodParameter {
-    fn clone(&self) -> Self {
-        let default_value: Option<Arc<Mutex<Box<dyn RuntimeValue>>>> = match &self.default_value {
-            Some(value) => {
-                let value = value
-                    .lock()
-                    .expect("meth_param_clone: failed to get default value");
-                Some(Arc::new(Mutex::new(dyn_clone::clone_box(&**value))))
-            }
-            None => None,
-        };
-        Self {
-            name: self.name.clone(),
-            default_value,
-        }
+impl ClassMethod {
+    pub fn to_function(&self, declaration_env: EnvironmentHandle) -> FunctionValue {
+        FunctionValue::new(
+            self.name.clone(),
+            self.args.iter().map(FunctionParameter::from).collect(),
+            declaration_env,
+            self.body.clone(),
+        )
     }
 }
 
+/// Method of a class declared by the vm, see `ClassValue::get_compiled_method`
 #[derive(Clone, Debug, Serialize)]
-pub struct ClassMethod {
-    pub name: String,
-    pub args: Vec<ClassMethodParameter>,
-    pub body: Box<Node>,
+pub struct CompiledMethod {
+    pub closure: Arc<ClosureValue>,
     pub is_static: bool,
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct ClassValue {
-    kind: ValueType,
     pub name: String,
-    pub super_class: Option<Box<ClassValue>>,
+    pub declaration_env: EnvironmentHandle,
+    pub super_class: Option<Arc<ClassValue>>,
+    /// Properties of the instances
     pub properties: Vec<ClassProperty>,
+    /// Static properties are shared by every copy of the class, like the fields of an object
+    pub static_properties: ObjectValue,
     pub methods: HashMap<String, ClassMethod>,
-}
-
-impl RuntimeValue for ClassValue {
-    fn kind(&self) -> ValueType {
-        self.kind
-    }
-
-    fn into_any(&self) -> Box<dyn std::any::Any> {
-        Box::new(dyn_clone::clone(self))
-    }
+    pub compiled_methods: HashMap<String, CompiledMethod>,
 }
 
 impl Default for ClassValue {
     fn default() -> Self {
         Self {
-            kind: ValueType::Class,
             name: "".to_string(),
+            declaration_env: EnvironmentHandle::default(),
             super_class: None,
             properties: Vec::new(),
+            static_properties: ObjectValue::default(),
             methods: HashMap::new(),
+            compiled_methods: HashMap::new(),
         }
     }
 }
 
 impl ClassValue {
     pub fn insert_property(&mut self, property: ClassProperty) {
+        if property.is_static {
+            return self.assign_static_property(property.name, property.value);
+        }
         if let Some(index) = self.properties.iter().position(|x| x.name == property.name) {
             self.properties.remove(index);
         }
@@ -111,33 +100,91 @@ impl ClassValue {
         }
     }
 
-    pub fn copy_methods(&mut self, target_class: &ClassValue) {
-        for (_, method) in target_class
-            .methods
-            .iter()
-            .filter(|method| method.0 != "__new__" && !method.1.is_static)
-        {
-            self.insert_method(method.clone());
+    /// Looks up an instance method through the class chain,
+    /// the method closes over the environment of the class that defines it
+    pub fn get_method(self: &Arc<Self>, method_name: &str) -> Option<FunctionValue> {
+        match self.methods.get(method_name) {
+            Some(method) if !method.is_static => {
+                let mut function = method.to_function(self.declaration_env.clone());
+                function.class = Some(self.clone());
+                Some(function)
+            }
+            _ => self
+                .super_class
+                .as_ref()
+                .and_then(|super_class| super_class.get_method(method_name)),
+        }
+    }
+
+    /// Same as `get_method` for the classes declared by the vm
+    pub fn get_compiled_method(self: &Arc<Self>, method_name: &str) -> Option<ClosureValue> {
+        match self.compiled_methods.get(method_name) {
+            Some(method) if !method.is_static => {
+                let mut closure = (*method.closure).clone();
+                closure.class = Some(self.clone());
+                Some(closure)
+            }
+            _ => self
+                .super_class
+                .as_ref()
+                .and_then(|super_class| super_class.get_compiled_method(method_name)),
+        }
+    }
+
+    pub fn get_static_compiled_method(&self, method_name: &str) -> Option<ClosureValue> {
+        match self.compiled_methods.get(method_name) {
+            Some(method) if method.is_static => Some((*method.closure).clone()),
+            _ => None,
+        }
+    }
+
+    /// Classes are shared by reference, every evaluation of a declaration creates a new one
+    pub fn is_same(self: &Arc<Self>, other: &Arc<ClassValue>) -> bool {
+        Arc::ptr_eq(self, other)
+    }
+
+    /// Whether the class is `other` or inherits from it
+    pub fn is_subclass_of(self: &Arc<Self>, other: &Arc<ClassValue>) -> bool {
+        self.is_same(other)
+            || self
+                .super_class
+                .as_ref()
+                .is_some_and(|super_class| super_class.is_subclass_of(other))
+    }
+
+    pub fn trace(&self, tracer: &mut Tracer) {
+        tracer.trace_environment(&self.declaration_env);
+        if let Some(super_class) = &self.super_class {
+            tracer.trace_shared(super_class, |class, tracer| class.trace(tracer));
+        }
+        for property in &self.properties {
+            tracer.trace_value(&property.value);
+        }
+        self.static_properties.trace(tracer);
+        for method in self.compiled_methods.values() {
+            tracer.trace_shared(&method.closure, |closure, tracer| closure.trace(tracer));
         }
     }
 
     pub fn get_static_property(&self, property_name: String) -> Option<ClassProperty> {
-        self.properties
-            .iter()
-            .find(|prop| prop.name == property_name && prop.is_static)
-            .cloned()
+        let value = self.static_properties.get_property(&property_name)?;
+        Some(ClassProperty {
+            name: property_name,
+            value,
+            is_static: true,
+        })
+    }
+
+    /// Updates the static property, or creates it if it doesn't exist yet
+    pub fn assign_static_property(&self, property_name: String, value: Value) {
+        self.static_properties.assign_property(property_name, value);
     }
 
     pub fn get_static_method(&self, method_name: String) -> Option<ClassMethod> {
         let method = self
             .methods
             .iter()
-            .find(|prop| prop.0.clone() == method_name && prop.1.is_static);
-        if method.is_none() {
-            return None;
-        }
-
-        let method = method.unwrap();
+            .find(|prop| prop.0.clone() == method_name && prop.1.is_static)?;
 
         Some(method.1.clone())
     }
@@ -1,47 +1,52 @@
-use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
-};
+use std::{collections::HashMap, sync::Arc};
 
 use serde::Serialize;
 
-use super::{RuntimeValue, ValueType};
+use crate::Tracer;
 
-pub type ClassInstanceMapValue = Arc<Mutex<Box<dyn RuntimeValue>>>;
+use super::{ClassValue, Key, ObjectValue, Value};
 
+/// Instances are shared by reference, clones point to the same fields
 #[derive(Debug, Clone, Serialize)]
 pub struct ClassInstanceValue {
-    kind: ValueType,
-    class_name: String,
-    map: HashMap<String, ClassInstanceMapValue>,
+    class: Arc<ClassValue>,
+    fields: ObjectValue,
 }
 
-impl RuntimeValue for ClassInstanceValue {
-    fn kind(&self) -> ValueType {
-        self.kind
-    }
-
-    fn into_any(&self) -> Box<dyn std::any::Any> {
-        Box::new(dyn_clone::clone(self))
-    }
-}
-
-impl From<(String, HashMap<String, ClassInstanceMapValue>)> for ClassInstanceValue {
-    fn from((class_name, map): (String, HashMap<String, ClassInstanceMapValue>)) -> Self {
+impl From<(Arc<ClassValue>, HashMap<Key, Value>)> for ClassInstanceValue {
+    fn from((class, map): (Arc<ClassValue>, HashMap<Key, Value>)) -> Self {
         Self {
-            kind: ValueType::Object,
-            class_name,
-            map,
+            class,
+            fields: ObjectValue::from(map),
         }
     }
 }
 
 impl ClassInstanceValue {
-    pub fn map(&self) -> HashMap<String, ClassInstanceMapValue> {
-        self.map.clone()
+    pub fn map(&self) -> HashMap<Key, Value> {
+        self.fields.map()
+    }
+
+    /// Fields of the instance, methods are looked up on the class instead
+    pub fn fields(&self) -> &ObjectValue {
+        &self.fields
+    }
+
+    /// Whether both values point to the same instance
+    pub fn is_same(&self, other: &ClassInstanceValue) -> bool {
+        self.fields.is_same(&other.fields)
+    }
+
+    pub fn class(&self) -> &Arc<ClassValue> {
+        &self.class
     }
 
     pub fn class_name(&self) -> String {
-        self.class_name.clone()
+        self.class.name.clone()
+    }
+
+    pub fn trace(&self, tracer: &mut Tracer) {
+        tracer.trace_shared(&self.class, |class, tracer| class.trace(tracer));
+        self.fields.trace(tracer);
     }
 }
@@ -0,0 +1,77 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+
+use crate::{ClassValue, EnvironmentHandle, FunctionProto, Tracer, ValueType};
+
+use super::Value;
+
+/// Variable captured by a closure, shared by the frame that declares it and every closure
+/// that captures it. Empty until the declaration runs
+pub type Cell = Arc<Mutex<Option<Value>>>;
+
+/// Function compiled for the vm together with the variables it captured
+#[derive(Clone)]
+pub struct ClosureValue {
+    pub function: Arc<FunctionProto>,
+    /// Created once per evaluation of the function expression, copies of the closure share it
+    pub upvalues: Arc<[Cell]>,
+    /// Environment of the module, names that aren't local are looked up in it
+    pub globals: EnvironmentHandle,
+    /// `self` of the frame that created the closure, used unless the closure is called as a method
+    pub receiver: Option<Cell>,
+    /// Class of the method that created the closure
+    pub enclosing_class: Option<Arc<ClassValue>>,
+    /// Class that defines the method, `super` is resolved against its super class
+    pub class: Option<Arc<ClassValue>>,
+}
+
+impl ClosureValue {
+    pub fn name(&self) -> &str {
+        &self.function.name
+    }
+
+    /// Whether both closures come from the same evaluation of the function expression,
+    /// methods are copied to set their class but keep the upvalues of the original
+    pub fn is_same(&self, other: &ClosureValue) -> bool {
+        Arc::ptr_eq(&self.function, &other.function) && Arc::ptr_eq(&self.upvalues, &other.upvalues)
+    }
+
+    pub fn trace(&self, tracer: &mut Tracer) {
+        tracer.trace_environment(&self.globals);
+        for cell in self.upvalues.iter().chain(&self.receiver) {
+            tracer.trace_locked(cell, |value, tracer| {
+                if let Some(value) = value {
+                    tracer.trace_value(value);
+                }
+            });
+        }
+        for class in self.enclosing_class.iter().chain(&self.class) {
+            tracer.trace_shared(class, |class, tracer| class.trace(tracer));
+        }
+    }
+}
+
+impl Serialize for ClosureValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("Closure", 2)?;
+        state.serialize_field("name", self.name())?;
+        state.serialize_field("kind", &ValueType::Function)?;
+        state.end()
+    }
+}
+
+impl fmt::Debug for ClosureValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClosureValue")
+            .field("name", &self.name())
+            .field("upvalues", &self.upvalues.len())
+            .finish()
+    }
+}
@@ -1,17 +1,20 @@
 use std::{
     fmt::{self, Debug, Formatter},
-    sync::{Arc, Mutex},
+    sync::Arc,
 };
 
 use pl_ast::Node;
 use serde::Serialize;
 
-use crate::{EnvironmentId, RuntimeValue, ValueType};
+use crate::{ClassMethodParameter, ClassValue, EnvironmentHandle, Tracer};
 
 #[derive(Clone, Serialize)]
 pub struct FunctionParameter {
     pub name: String,
-    pub default_value: Option<Arc<Mutex<Box<dyn RuntimeValue>>>>,
+    /// Evaluated at call time when the argument is omitted
+    pub default_value: Option<Box<Node>>,
+    /// Collects the remaining arguments into an array, e.g. `...parts`
+    pub is_rest: bool,
 }
 
 impl Debug for FunctionParameter {
@@ -19,15 +22,35 @@ impl Debug for FunctionParameter {
         f.debug_struct("FunctionParameter")
             .field("name", &self.name)
             .field("default_value", &self.default_value)
+            .field("is_rest", &self.is_rest)
             .finish()
     }
 }
 
 impl FunctionParameter {
-    pub fn new(name: String, default_value: Option<Arc<Mutex<Box<dyn RuntimeValue>>>>) -> Self {
+    pub fn new(name: String, default_value: Option<Box<Node>>) -> Self {
         Self {
             name,
             default_value,
+            is_rest: false,
+        }
+    }
+
+    pub fn rest(name: String) -> Self {
+        Self {
+            name,
+            default_value: None,
+            is_rest: true,
+        }
+    }
+}
+
+impl From<&ClassMethodParameter> for FunctionParameter {
+    fn from(parameter: &ClassMethodParameter) -> Self {
+        Self {
+            name: parameter.name.clone(),
+            default_value: parameter.default_value.clone(),
+            is_rest: parameter.is_rest,
         }
     }
 }
@@ -46,36 +69,35 @@ impl FunctionParameter {
 
 #[derive(Debug, Clone, Serialize)]
 pub struct FunctionValue {
-    kind: ValueType,
     pub name: String,
     pub parameters: Vec<FunctionParameter>,
-    pub declaration_env: EnvironmentId,
-    pub body: Box<Node>,
+    pub declaration_env: EnvironmentHandle,
+    /// Shared by the values created from the same declaration, calls don't copy it
+    pub body: Arc<Node>,
+    /// Class that defines the method, `super` is resolved against its super class
+    pub class: Option<Arc<ClassValue>>,
 }
 
 impl FunctionValue {
     pub fn new(
         name: String,
         parameters: Vec<FunctionParameter>,
-        declaration_env: EnvironmentId,
-        body: Box<Node>,
+        declaration_env: EnvironmentHandle,
+        body: Arc<Node>,
     ) -> Self {
         Self {
-            kind: ValueType::Function,
             name,
             parameters,
             declaration_env,
             body,
+            class: None,
         }
     }
-}
 
-impl RuntimeValue for FunctionValue {
-    fn kind(&self) -> ValueType {
-        self.kind
-    }
-
-    fn into_any(&self) -> Box<dyn std::any::Any> {
-        Box::new(dyn_clone::clone(self))
+    pub fn trace(&self, tracer: &mut Tracer) {
+        tracer.trace_environment(&self.declaration_env);
+        if let Some(class) = &self.class {
+            tracer.trace_shared(class, |class, tracer| class.trace(tracer));
+        }
     }
 }
@@ -1,33 +1,24 @@
-use std::{any::Any, fmt::Debug};
+use std::{collections::HashMap, sync::Arc};
 
-use dyn_clone::DynClone;
+use serde::Serialize;
 
-use erased_serde::serialize_trait_object;
+use crate::Tracer;
 
 mod array;
-mod bool;
 mod class;
 mod class_instance;
-mod decimal;
+mod closure;
 mod function;
-mod integer;
 mod native_fn;
-mod null;
 mod object;
-mod string;
 
 pub use array::*;
-pub use bool::*;
 pub use class::*;
 pub use class_instance::*;
-pub use decimal::*;
+pub use closure::*;
 pub use function::*;
-pub use integer::*;
 pub use native_fn::*;
-pub use null::*;
 pub use object::*;
-use serde::Serialize;
-pub use string::*;
 
 #[derive(Debug, PartialEq, Clone, Copy, Serialize)]
 pub enum ValueType {
@@ -44,10 +35,186 @@ pub enum ValueType {
     ClassInstance,
 }
 
-pub trait RuntimeValue: DynClone + Debug + Send + Sync + erased_serde::Serialize {
-    fn kind(&self) -> ValueType;
-    #[allow(clippy::wrong_self_convention)]
-    fn into_any(&self) -> Box<dyn Any>;
+/// Runtime value of a script. Scalars are stored inline and everything else is
+/// reference counted, so cloning a value never copies more than a pointer
+#[derive(Debug, Clone, Default, Serialize)]
+pub enum Value {
+    #[default]
+    Null,
+    Integer(isize),
+    Decimal(f64),
+    Boolean(bool),
+    String(Arc<str>),
+    NativeFn(Arc<NativeFnValue>),
+    Function(Arc<FunctionValue>),
+    /// Function of the bytecode vm, see `crate::Vm`
+    Closure(Arc<ClosureValue>),
+    /// Arrays, objects and instances are shared by reference, clones point to the same data
+    Array(ArrayValue),
+    Object(ObjectValue),
+    Class(Arc<ClassValue>),
+    ClassInstance(Arc<ClassInstanceValue>),
+}
+
+impl Value {
+    pub fn kind(&self) -> ValueType {
+        match self {
+            Value::Null => ValueType::Null,
+            Value::Integer(_) => ValueType::Integer,
+            Value::Decimal(_) => ValueType::Decimal,
+            Value::Boolean(_) => ValueType::Boolean,
+            Value::String(_) => ValueType::String,
+            Value::NativeFn(_) => ValueType::NativeFn,
+            Value::Function(_) | Value::Closure(_) => ValueType::Function,
+            Value::Array(_) => ValueType::Array,
+            Value::Object(_) => ValueType::Object,
+            Value::Class(_) => ValueType::Class,
+            Value::ClassInstance(_) => ValueType::ClassInstance,
+        }
+    }
+
+    /// Reports the environments and shared values this value references
+    pub fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            Value::Function(function) => {
+                tracer.trace_shared(function, |function, tracer| function.trace(tracer))
+            }
+            Value::Closure(closure) => {
+                tracer.trace_shared(closure, |closure, tracer| closure.trace(tracer))
+            }
+            Value::Class(class) => tracer.trace_shared(class, |class, tracer| class.trace(tracer)),
+            Value::Array(array) => array.trace(tracer),
+            Value::Object(object) => object.trace(tracer),
+            Value::ClassInstance(instance) => {
+                tracer.trace_shared(instance, |instance, tracer| instance.trace(tracer))
+            }
+            _ => {}
+        }
+    }
+
+    /// Replaces the contents of an array, object or instance with the ones of a value
+    /// of the same type, used when a method assigns to `self`.
+    /// Returns `false` if the value can't take the place of this one
+    pub fn replace_contents(&self, value: &Value) -> bool {
+        match (self, value) {
+            (Value::Array(target), Value::Array(source)) => {
+                target.replace_elements(source.value());
+            }
+            (Value::Object(target), Value::Object(source)) => target.replace_map(source.map()),
+            (Value::ClassInstance(target), Value::ClassInstance(source))
+                if Arc::ptr_eq(target.class(), source.class()) =>
+            {
+                target.fields().replace_map(source.map())
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Copies arrays and objects including the nested ones, other values stay shared
+    pub fn deep_copy(&self) -> Value {
+        self.deep_copy_with(&mut HashMap::new())
+    }
+
+    /// `copies` maps the arrays and objects copied so far to their copies,
+    /// so a value that contains itself is copied into a copy that contains itself
+    fn deep_copy_with(&self, copies: &mut HashMap<usize, Value>) -> Value {
+        match self {
+            Value::Array(array) => {
+                if let Some(copy) = copies.get(&array.id()) {
+                    return copy.clone();
+                }
+                let copy = ArrayValue::from(vec![]);
+                copies.insert(array.id(), Value::from(copy.clone()));
+                for item in array.value() {
+                    copy.append_element(item.deep_copy_with(copies));
+                }
+                Value::from(copy)
+            }
+            Value::Object(object) => {
+                if let Some(copy) = copies.get(&object.id()) {
+                    return copy.clone();
+                }
+                let copy = ObjectValue::from(HashMap::new());
+                copies.insert(object.id(), Value::from(copy.clone()));
+                for (key, item) in object.map() {
+                    copy.assign_property(key, item.deep_copy_with(copies));
+                }
+                Value::from(copy)
+            }
+            value => value.clone(),
+        }
+    }
+}
+
+impl From<isize> for Value {
+    fn from(value: isize) -> Self {
+        Value::Integer(value)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Decimal(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Boolean(value)
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value.into())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.into())
+    }
+}
+
+impl From<NativeFnValue> for Value {
+    fn from(value: NativeFnValue) -> Self {
+        Value::NativeFn(Arc::new(value))
+    }
+}
+
+impl From<FunctionValue> for Value {
+    fn from(value: FunctionValue) -> Self {
+        Value::Function(Arc::new(value))
+    }
+}
+
+impl From<ClosureValue> for Value {
+    fn from(value: ClosureValue) -> Self {
+        Value::Closure(Arc::new(value))
+    }
+}
+
+impl From<ArrayValue> for Value {
+    fn from(value: ArrayValue) -> Self {
+        Value::Array(value)
+    }
+}
+
+impl From<ObjectValue> for Value {
+    fn from(value: ObjectValue) -> Self {
+        Value::Object(value)
+    }
+}
+
+impl From<ClassValue> for Value {
+    fn from(value: ClassValue) -> Self {
+        Value::Class(Arc::new(value))
+    }
 }
 
-serialize_trait_object!(RuntimeValue);
+impl From<ClassInstanceValue> for Value {
+    fn from(value: ClassInstanceValue) -> Self {
+        Value::ClassInstance(Arc::new(value))
+    }
+}
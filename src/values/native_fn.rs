@@ -1,18 +1,17 @@
-use std::{
-    fmt,
-    sync::{Arc, Mutex},
-};
+use std::{fmt, sync::Arc};
 
 use serde::{ser::SerializeStruct, Serialize, Serializer};
 
-use crate::{RuntimeValue, ValueType};
+use crate::{InterpreterError, Value, ValueType};
+
+/// What natives can ask of the engine that calls them
+pub trait NativeContext {
+    /// Result of the `__str__` method of the instance, `None` if its class doesn't define one
+    fn call_str_method(&mut self, instance: &Value) -> Result<Option<String>, InterpreterError>;
+}
 
 pub type ClosureType = Arc<
-    Mutex<
-        dyn Fn(Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>) -> Arc<Mutex<Box<dyn RuntimeValue>>>
-            + Send
-            + Sync,
-    >,
+    dyn Fn(&mut dyn NativeContext, Vec<Value>) -> Result<Value, InterpreterError> + Send + Sync,
 >;
 
 #[derive(Clone)]
@@ -27,30 +26,26 @@ impl WithFnCall<ClosureType> {
 
     pub fn run(
         &self,
-        args: Vec<Arc<Mutex<Box<dyn RuntimeValue>>>>,
-    ) -> Arc<Mutex<Box<dyn RuntimeValue>>> {
-        (self.fc.lock().expect("with_fn_call: failed to get fc"))(args)
+        context: &mut dyn NativeContext,
+        args: Vec<Value>,
+    ) -> Result<Value, InterpreterError> {
+        (self.fc)(context, args)
     }
 }
 
 #[derive(Clone)]
 pub struct NativeFnValue {
     pub name: String,
-    kind: ValueType,
     call: WithFnCall<ClosureType>,
 }
 
 impl NativeFnValue {
     pub fn new(name: String, call: WithFnCall<ClosureType>) -> Self {
-        Self {
-            kind: ValueType::NativeFn,
-            name,
-            call,
-        }
+        Self { name, call }
     }
 
-    pub fn callee(&self) -> WithFnCall<ClosureType> {
-        self.call.clone()
+    pub fn callee(&self) -> &WithFnCall<ClosureType> {
+        &self.call
     }
 }
 
@@ -61,7 +56,7 @@ impl Serialize for NativeFnValue {
     {
         let mut state = serializer.serialize_struct("NativeFn", 2)?;
         state.serialize_field("name", &self.name)?;
-        state.serialize_field("kind", &self.kind())?;
+        state.serialize_field("kind", &ValueType::NativeFn)?;
         state.end()
     }
 }
@@ -69,17 +64,7 @@ impl Serialize for NativeFnValue {
 impl fmt::Debug for NativeFnValue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("NativeFnValue")
-            .field("kind", &self.kind)
+            .field("name", &self.name)
             .finish()
     }
 }
-
-impl RuntimeValue for NativeFnValue {
-    fn kind(&self) -> ValueType {
-        self.kind
-    }
-
-    fn into_any(&self) -> Box<dyn std::any::Any> {
-        Box::new(dyn_clone::clone(self))
-    }
-}
@@ -5,52 +5,73 @@ use std::{
 
 use serde::Serialize;
 
-use super::{RuntimeValue, ValueType};
+use crate::Tracer;
+
+use super::Value;
 
 pub type Key = String;
-pub type Value = Arc<Mutex<Box<dyn RuntimeValue>>>;
 
-#[derive(Debug, Clone, Serialize)]
+/// Objects are shared by reference, clones point to the same map
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct ObjectValue {
-    kind: ValueType,
-    map: HashMap<Key, Value>,
-}
-
-impl RuntimeValue for ObjectValue {
-    fn kind(&self) -> ValueType {
-        self.kind
-    }
-
-    fn into_any(&self) -> Box<dyn std::any::Any> {
-        Box::new(dyn_clone::clone(self))
-    }
+    map: Arc<Mutex<HashMap<Key, Value>>>,
 }
 
 impl From<HashMap<Key, Value>> for ObjectValue {
     fn from(map: HashMap<Key, Value>) -> Self {
         Self {
-            kind: ValueType::Object,
-            map,
+            map: Arc::new(Mutex::new(map)),
         }
     }
 }
 
 impl ObjectValue {
     pub fn map(&self) -> HashMap<Key, Value> {
-        self.map.clone()
+        self.map
+            .lock()
+            .expect("object.map(): failed to get map")
+            .clone()
     }
 
-    pub fn get_property(&self, key: Key) -> Option<Value> {
-        if let Some(property) = self.map.get(&key) {
-            let value = property
-                .lock()
-                .expect("object.get_property(): failed to get property value");
-            return Some(Arc::new(Mutex::new(dyn_clone::clone_box(&**value))));
-        }
-        None
+    pub fn get_property(&self, key: &str) -> Option<Value> {
+        self.map
+            .lock()
+            .expect("object.get_property(): failed to get map")
+            .get(key)
+            .cloned()
+    }
+
+    /// Whether both values point to the same object
+    pub fn is_same(&self, other: &ObjectValue) -> bool {
+        Arc::ptr_eq(&self.map, &other.map)
+    }
+
+    /// Address of the map, the same for every value that points to the object
+    pub fn id(&self) -> usize {
+        Arc::as_ptr(&self.map) as usize
+    }
+
+    /// Updates the property, or creates it if it doesn't exist yet
+    pub fn assign_property(&self, key: Key, value: Value) {
+        self.map
+            .lock()
+            .expect("object.assign_property(): failed to get map")
+            .insert(key, value);
+    }
+
+    /// Replaces all of the properties, every value that points to the object sees the new ones
+    pub fn replace_map(&self, map: HashMap<Key, Value>) {
+        *self
+            .map
+            .lock()
+            .expect("object.replace_map(): failed to get map") = map
     }
 
-    pub fn assign_property(&mut self, key: Key, value: Value) {
-        self.map.entry(key).and_modify(|val| *val = value);
+    pub fn trace(&self, tracer: &mut Tracer) {
+        tracer.trace_locked(&self.map, |map, tracer| {
+            for value in map.values() {
+                tracer.trace_value(value);
+            }
+        });
     }
 }
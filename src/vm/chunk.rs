@@ -0,0 +1,171 @@
+use std::{fmt, ops::Range, sync::Arc};
+
+use pl_ast::{BinaryOperator, LogicalOperator};
+
+use crate::{InterpreterError, Key, Value};
+
+/// Variable resolved by the compiler
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Variable {
+    /// Slot of the running frame
+    Local(usize),
+    /// Cell captured by the running closure
+    Upvalue(usize),
+    /// Name in the module environment, looked up when the instruction runs
+    Global(usize),
+    /// `self` of the running frame, a global named `self` outside of methods
+    Receiver(usize),
+}
+
+/// Instructions operate on the value stack of the frame, jump targets are code indices
+#[derive(Debug, Clone)]
+pub enum Instruction {
+    Constant(usize),
+    Null,
+    Pop,
+    Get(Variable),
+    /// Assigns the value on top of the stack and leaves it there
+    Set(Variable),
+    /// Declares a variable, the boolean marks a constant
+    Declare(Variable, bool),
+    /// Undeclares a local so the next loop iteration can declare it again
+    ClearLocal(usize),
+    /// Jumps over the default value of a parameter that got an argument
+    JumpIfDeclared(usize, usize),
+    Array(usize),
+    /// Object with the keys of the shape, values are on the stack in the same order
+    Object(usize),
+    /// `object.name`, the name is a constant
+    GetProperty(usize),
+    /// `object[key]`
+    GetIndex,
+    /// Pushes the current value of `object[key]` for compound assignments
+    GetForUpdate {
+        computed: bool,
+    },
+    /// `object[key] = value`
+    SetMember {
+        computed: bool,
+    },
+    Binary(BinaryOperator),
+    /// Unary minus, the index points to the error for values that can't be negated
+    Negate(usize),
+    Not(usize),
+    Jump(usize),
+    JumpIfFalse(usize),
+    /// Skips the right side when the left one decides the result
+    Logical(LogicalOperator, usize),
+    CheckBoolean,
+    IterStart,
+    /// Pushes the next item or jumps to the end of the loop
+    IterNext(usize),
+    IterEnd,
+    Call(usize),
+    /// Method name and argument count, the object is on top of the arguments
+    CallMethod(usize, usize),
+    SuperCall(usize, usize),
+    Closure(usize),
+    Class(usize),
+    /// Adds the methods of the class on top of the stack to the builtin values, see `Prototypes`
+    Extend,
+    Return,
+    Throw,
+    Try(usize),
+    Import(usize),
+    Export(usize),
+    /// Fails if a module is being loaded, only top level declarations can be exported
+    CheckExport(usize),
+    /// Fails with one of the errors of the function, for code that can't run
+    Raise(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Parameter {
+    pub name: String,
+    pub has_default: bool,
+    pub is_rest: bool,
+}
+
+/// Where a closure takes a captured variable from when it is created
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capture {
+    Local(usize),
+    Upvalue(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Upvalue {
+    pub capture: Capture,
+    pub name: String,
+    /// Upvalue used while the captured variable isn't declared yet, a closure can run
+    /// before the declaration it captured and the tree walker finds the outer variable then
+    pub fallback: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ClassMember {
+    /// The value is on the stack, in the order of the class body
+    Property { name: String, is_static: bool },
+    Method {
+        name: String,
+        function: usize,
+        is_static: bool,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct ClassProto {
+    pub name: String,
+    /// The super class is below the property values on the stack
+    pub has_super_class: bool,
+    pub members: Vec<ClassMember>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TryBlock {
+    pub body: Range<usize>,
+    /// Starts with taking the caught value from the stack
+    pub handler: Option<Range<usize>>,
+    pub finalizer: Option<Range<usize>>,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportProto {
+    pub path: String,
+    /// Pushed in reverse order, so the first name is on top. Empty for `import "path"`
+    pub names: Vec<String>,
+}
+
+/// Compiled function body, parameters take the first local slots
+#[derive(Default)]
+pub struct FunctionProto {
+    pub name: String,
+    pub parameters: Vec<Parameter>,
+    pub code: Vec<Instruction>,
+    pub constants: Vec<Value>,
+    pub names: Vec<String>,
+    /// Names of the local slots
+    pub locals: Vec<String>,
+    pub upvalues: Vec<Upvalue>,
+    pub functions: Vec<Arc<FunctionProto>>,
+    pub classes: Vec<ClassProto>,
+    pub shapes: Vec<Vec<Key>>,
+    pub tries: Vec<TryBlock>,
+    pub imports: Vec<ImportProto>,
+    /// Errors the tree walker reports once the code runs, see `Instruction::Raise`
+    pub errors: Vec<InterpreterError>,
+}
+
+impl fmt::Debug for FunctionProto {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FunctionProto")
+            .field("name", &self.name)
+            .field("parameters", &self.parameters)
+            .field("code", &self.code)
+            .field("locals", &self.locals)
+            .field("upvalues", &self.upvalues)
+            .field("functions", &self.functions)
+            .finish()
+    }
+}
@@ -0,0 +1,880 @@
+use std::{collections::HashMap, sync::Arc};
+
+use pl_ast::{AssignmentOperator, Node, UnaryOperator};
+
+use crate::{get_binary_operator, InterpreterError, Prototypes, Value};
+
+use super::{
+    Capture, ClassMember, ClassProto, FunctionProto, ImportProto, Instruction, Parameter, TryBlock,
+    Upvalue, Variable,
+};
+
+struct Local {
+    name: String,
+    slot: usize,
+    is_constant: bool,
+    /// Code of the same function only sees the local after its declaration,
+    /// nested functions see it for the whole block as they usually run later
+    is_declared: bool,
+}
+
+struct Loop {
+    /// Target of `continue`
+    start: usize,
+    /// Jumps to patch with the end of the loop
+    breaks: Vec<usize>,
+}
+
+struct FunctionState {
+    proto: FunctionProto,
+    /// Block scopes, the outermost one holds the parameters.
+    /// The top level of a script has none, its declarations are globals
+    scopes: Vec<Vec<Local>>,
+    loops: Vec<Loop>,
+    names: HashMap<String, usize>,
+}
+
+impl FunctionState {
+    fn new(name: String) -> Self {
+        Self {
+            proto: FunctionProto {
+                name,
+                ..Default::default()
+            },
+            scopes: vec![],
+            loops: vec![],
+            names: HashMap::new(),
+        }
+    }
+
+    fn find_local(&self, name: &str, declared_only: bool) -> Option<&Local> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter())
+            .find(|local| local.name == name && (local.is_declared || !declared_only))
+    }
+
+    /// Local of the scopes below `depth`, declared or not, along with the index of its scope
+    fn find_local_below(&self, name: &str, depth: usize) -> Option<(usize, &Local)> {
+        self.scopes
+            .iter()
+            .enumerate()
+            .take(depth)
+            .rev()
+            .find_map(|(depth, scope)| {
+                scope
+                    .iter()
+                    .find(|local| local.name == name)
+                    .map(|local| (depth, local))
+            })
+    }
+}
+
+/// Compiles the syntax tree into functions for the vm. Errors the tree walker reports
+/// while running, e.g. an invalid parameter, are compiled into `Instruction::Raise`
+#[derive(Default)]
+pub struct Compiler {
+    functions: Vec<FunctionState>,
+}
+
+impl Compiler {
+    pub fn compile_program(program: &Node) -> FunctionProto {
+        let mut compiler = Compiler {
+            functions: vec![FunctionState::new("main".into())],
+        };
+        match program {
+            Node::Program(statements) => {
+                for statement in statements {
+                    compiler.compile_statement(statement);
+                }
+            }
+            statement => compiler.compile_statement(statement),
+        }
+        compiler.functions.pop().unwrap().proto
+    }
+
+    fn state(&mut self) -> &mut FunctionState {
+        self.functions.last_mut().unwrap()
+    }
+
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        let code = &mut self.state().proto.code;
+        code.push(instruction);
+        code.len() - 1
+    }
+
+    fn position(&mut self) -> usize {
+        self.state().proto.code.len()
+    }
+
+    /// Points the jump at `index` to the current position
+    fn patch(&mut self, index: usize) {
+        let target = self.position();
+        match &mut self.state().proto.code[index] {
+            Instruction::Jump(jump)
+            | Instruction::JumpIfFalse(jump)
+            | Instruction::JumpIfDeclared(_, jump)
+            | Instruction::Logical(_, jump)
+            | Instruction::IterNext(jump) => *jump = target,
+            instruction => panic!("patch: {:?} is not a jump", instruction),
+        }
+    }
+
+    fn constant(&mut self, value: Value) -> usize {
+        let constants = &mut self.state().proto.constants;
+        constants.push(value);
+        constants.len() - 1
+    }
+
+    fn name(&mut self, name: &str) -> usize {
+        let state = self.state();
+        if let Some(index) = state.names.get(name) {
+            return *index;
+        }
+        state.proto.names.push(name.to_string());
+        let index = state.proto.names.len() - 1;
+        state.names.insert(name.to_string(), index);
+        index
+    }
+
+    fn raise(&mut self, error: InterpreterError) {
+        let errors = &mut self.state().proto.errors;
+        errors.push(error);
+        let index = errors.len() - 1;
+        self.emit(Instruction::Raise(index));
+    }
+
+    fn error(&mut self, error: InterpreterError) -> usize {
+        let errors = &mut self.state().proto.errors;
+        errors.push(error);
+        errors.len() - 1
+    }
+
+    /// Opens a block scope with slots for every declaration directly inside of it
+    fn begin_scope(&mut self, statements: &[Box<Node>]) {
+        self.state().scopes.push(vec![]);
+        for statement in statements {
+            let statement = match &**statement {
+                Node::ExportDeclaration(declaration) => declaration,
+                _ => statement,
+            };
+            match &**statement {
+                Node::VariableDeclaration(name, _, is_constant) => {
+                    self.add_local(name, *is_constant, false);
+                }
+                Node::FunctionDeclaration(name, ..) => {
+                    self.add_local(name, true, false);
+                }
+                Node::ClassDeclaration(name, ..) if !Prototypes::is_extendable(name) => {
+                    self.add_local(name, true, false);
+                }
+                Node::ImportDeclaration(names, _) => {
+                    for name in names {
+                        self.add_local(name, true, false);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn end_scope(&mut self) {
+        self.state().scopes.pop();
+    }
+
+    /// Slot of a new local, repeated declarations in a scope fail when they run
+    fn add_local(&mut self, name: &str, is_constant: bool, is_declared: bool) -> usize {
+        let state = self.state();
+        let scope = state.scopes.last_mut().unwrap();
+        if let Some(local) = scope.iter().find(|local| local.name == name) {
+            return local.slot;
+        }
+        let slot = state.proto.locals.len();
+        state.proto.locals.push(name.to_string());
+        scope.push(Local {
+            name: name.to_string(),
+            slot,
+            is_constant,
+            is_declared,
+        });
+        // a loop runs the block again, the previous iteration must not keep the local declared
+        if !state.loops.is_empty() {
+            self.emit(Instruction::ClearLocal(slot));
+        }
+        slot
+    }
+
+    /// Declares the value on top of the stack
+    fn declare(&mut self, name: &str, is_constant: bool) {
+        if self.state().scopes.is_empty() {
+            let name = self.name(name);
+            self.emit(Instruction::Declare(Variable::Global(name), is_constant));
+            return;
+        }
+        let local = self
+            .state()
+            .scopes
+            .last_mut()
+            .unwrap()
+            .iter_mut()
+            .find(|local| local.name == name);
+        let slot = match local {
+            Some(local) if local.is_declared => {
+                return self.raise(InterpreterError::VariableDeclarationExist(name.to_string()));
+            }
+            Some(local) => {
+                local.is_declared = true;
+                local.slot
+            }
+            // declarations that aren't directly in a block, e.g. `if x let y = 1`
+            None => self.add_local(name, is_constant, true),
+        };
+        self.emit(Instruction::Declare(Variable::Local(slot), is_constant));
+    }
+
+    /// Variable the name refers to and whether it's a constant
+    fn resolve_variable(&mut self, name: &str) -> (Variable, bool) {
+        let current = self.functions.len() - 1;
+        if let Some(local) = self.functions[current].find_local(name, true) {
+            return (Variable::Local(local.slot), local.is_constant);
+        }
+        if let Some((index, is_constant)) = self.resolve_upvalue(current, name) {
+            return (Variable::Upvalue(index), is_constant);
+        }
+        let index = self.name(name);
+        if name == "self" {
+            return (Variable::Receiver(index), false);
+        }
+        (Variable::Global(index), false)
+    }
+
+    fn resolve_upvalue(&mut self, function: usize, name: &str) -> Option<(usize, bool)> {
+        self.capture_variable(function, name, usize::MAX)
+    }
+
+    /// Captures the variable of the enclosing function, only looking at its scopes below `depth`
+    fn capture_variable(
+        &mut self,
+        function: usize,
+        name: &str,
+        depth: usize,
+    ) -> Option<(usize, bool)> {
+        if function == 0 {
+            return None;
+        }
+        let enclosing = function - 1;
+        let (capture, is_constant, fallback) =
+            match self.functions[enclosing].find_local_below(name, depth) {
+                Some((scope, local)) => {
+                    let (capture, is_constant) = (Capture::Local(local.slot), local.is_constant);
+                    // the closure may run before the declaration, the outer variable is used then
+                    let fallback = match local.is_declared {
+                        true => None,
+                        false => self
+                            .capture_variable(function, name, scope)
+                            .map(|(index, _)| index),
+                    };
+                    (capture, is_constant, fallback)
+                }
+                None => {
+                    let (index, is_constant) = self.resolve_upvalue(enclosing, name)?;
+                    let fallback = self.functions[enclosing].proto.upvalues[index].fallback;
+                    let fallback = fallback.map(|fallback| self.import_upvalue(function, fallback));
+                    (Capture::Upvalue(index), is_constant, fallback)
+                }
+            };
+        let index = self.add_upvalue(function, capture, name, fallback);
+        Some((index, is_constant))
+    }
+
+    /// Captures the upvalue of the enclosing function along with its fallbacks
+    fn import_upvalue(&mut self, function: usize, index: usize) -> usize {
+        let upvalue = &self.functions[function - 1].proto.upvalues[index];
+        let (name, fallback) = (upvalue.name.clone(), upvalue.fallback);
+        let fallback = fallback.map(|fallback| self.import_upvalue(function, fallback));
+        self.add_upvalue(function, Capture::Upvalue(index), &name, fallback)
+    }
+
+    fn add_upvalue(
+        &mut self,
+        function: usize,
+        capture: Capture,
+        name: &str,
+        fallback: Option<usize>,
+    ) -> usize {
+        let upvalues = &mut self.functions[function].proto.upvalues;
+        if let Some(index) = upvalues
+            .iter()
+            .position(|upvalue| upvalue.capture == capture)
+        {
+            return index;
+        }
+        upvalues.push(Upvalue {
+            capture,
+            name: name.to_string(),
+            fallback,
+        });
+        upvalues.len() - 1
+    }
+
+    fn compile_block(&mut self, statements: &[Box<Node>]) {
+        self.begin_scope(statements);
+        for statement in statements {
+            self.compile_statement(statement);
+        }
+        self.end_scope();
+    }
+
+    /// Statements leave the stack as it was
+    fn compile_statement(&mut self, node: &Node) {
+        match node {
+            Node::BlockStatement(statements) => self.compile_block(statements),
+            Node::VariableDeclaration(name, value, is_constant) => {
+                match value {
+                    Some(value) => self.compile_expression(value),
+                    None => {
+                        self.emit(Instruction::Null);
+                    }
+                }
+                self.declare(name, *is_constant);
+            }
+            Node::FunctionDeclaration(name, parameters, body) => {
+                if self.compile_closure(name, parameters, body) {
+                    self.declare(name, true);
+                }
+            }
+            Node::ClassDeclaration(name, super_class, body) => {
+                self.compile_class(name, super_class.as_deref(), body);
+                // classes named after a builtin type extend it, see `Prototypes`
+                if Prototypes::is_extendable(name) {
+                    self.emit(Instruction::Extend);
+                } else {
+                    self.declare(name, true);
+                }
+            }
+            Node::IfStatement(condition, body, alternate) => {
+                self.compile_expression(condition);
+                let skip_body = self.emit(Instruction::JumpIfFalse(0));
+                self.compile_statement(body);
+                match alternate {
+                    Some(alternate) => {
+                        let skip_alternate = self.emit(Instruction::Jump(0));
+                        self.patch(skip_body);
+                        self.compile_statement(alternate);
+                        self.patch(skip_alternate);
+                    }
+                    None => self.patch(skip_body),
+                }
+            }
+            Node::WhileStatement(condition, body) => {
+                let start = self.position();
+                self.compile_expression(condition);
+                let exit = self.emit(Instruction::JumpIfFalse(0));
+                self.compile_loop_body(start, body);
+                self.emit(Instruction::Jump(start));
+                self.patch(exit);
+                self.end_loop();
+            }
+            Node::ForInStatement(left, iterable, body) => {
+                self.compile_for_in_statement(left, iterable, body)
+            }
+            Node::TryStatement(block, handler, finalizer) => {
+                self.compile_try_statement(block, handler.as_deref(), finalizer.as_deref())
+            }
+            Node::ImportDeclaration(names, path) => {
+                let imports = &mut self.state().proto.imports;
+                imports.push(ImportProto {
+                    path: path.clone(),
+                    names: names.clone(),
+                });
+                let index = imports.len() - 1;
+                self.emit(Instruction::Import(index));
+                for name in names {
+                    self.declare(name, true);
+                }
+            }
+            Node::ExportDeclaration(declaration) => self.compile_export_declaration(declaration),
+            Node::ThrowStatement(value) => {
+                self.compile_expression(value);
+                self.emit(Instruction::Throw);
+            }
+            Node::ReturnStatement(value) => {
+                self.compile_expression(value);
+                self.emit(Instruction::Return);
+            }
+            Node::BreakStatement => match self.state().loops.last_mut() {
+                Some(_) => {
+                    let jump = self.emit(Instruction::Jump(0));
+                    self.state().loops.last_mut().unwrap().breaks.push(jump);
+                }
+                // a call turns it into `InvalidLoopControl`, like in the tree walker
+                None => self.raise(InterpreterError::Break),
+            },
+            Node::ContinueStatement => match self.state().loops.last() {
+                Some(current) => {
+                    let start = current.start;
+                    self.emit(Instruction::Jump(start));
+                }
+                None => self.raise(InterpreterError::Continue),
+            },
+            expression => {
+                self.compile_expression(expression);
+                self.emit(Instruction::Pop);
+            }
+        }
+    }
+
+    fn compile_loop_body(&mut self, start: usize, body: &Node) {
+        self.state().loops.push(Loop {
+            start,
+            breaks: vec![],
+        });
+        self.compile_statement(body);
+    }
+
+    fn end_loop(&mut self) {
+        let current = self.state().loops.pop().unwrap();
+        for jump in current.breaks {
+            self.patch(jump);
+        }
+    }
+
+    fn compile_for_in_statement(&mut self, left: &Node, iterable: &Node, body: &Node) {
+        let variable_name = match left {
+            Node::Identifier(name) => name,
+            _ => return self.raise(InterpreterError::UnexpectedNode(Box::new(left.clone()))),
+        };
+        self.compile_expression(iterable);
+        self.emit(Instruction::IterStart);
+        let start = self.emit(Instruction::IterNext(0));
+        self.state().loops.push(Loop {
+            start,
+            breaks: vec![],
+        });
+        // every iteration gets its own binding of the loop variable
+        self.state().scopes.push(vec![]);
+        let slot = self.add_local(variable_name, false, true);
+        self.emit(Instruction::Declare(Variable::Local(slot), false));
+        self.compile_statement(body);
+        self.end_scope();
+        self.emit(Instruction::Jump(start));
+        // `break` jumps here as well, so the iterator is always dropped
+        self.patch(start);
+        self.end_loop();
+        self.emit(Instruction::IterEnd);
+    }
+
+    fn compile_try_statement(
+        &mut self,
+        block: &Node,
+        handler: Option<&Node>,
+        finalizer: Option<&Node>,
+    ) {
+        let instruction = self.emit(Instruction::Try(0));
+        let body_start = self.position();
+        self.compile_statement(block);
+        let body = body_start..self.position();
+
+        let handler = handler.map(|handler| {
+            let start = self.position();
+            match handler {
+                Node::CatchClause(parameter, body) => {
+                    self.state().scopes.push(vec![]);
+                    match parameter {
+                        Some(parameter) => {
+                            let slot = self.add_local(parameter, false, true);
+                            self.emit(Instruction::Declare(Variable::Local(slot), false));
+                        }
+                        None => {
+                            self.emit(Instruction::Pop);
+                        }
+                    }
+                    self.compile_statement(body);
+                    self.end_scope();
+                }
+                handler => {
+                    self.emit(Instruction::Pop);
+                    self.raise(InterpreterError::UnexpectedNode(Box::new(handler.clone())));
+                }
+            }
+            start..self.position()
+        });
+
+        let finalizer = finalizer.map(|finalizer| {
+            let start = self.position();
+            self.compile_statement(finalizer);
+            start..self.position()
+        });
+
+        let end = self.position();
+        let tries = &mut self.state().proto.tries;
+        tries.push(TryBlock {
+            body,
+            handler,
+            finalizer,
+            end,
+        });
+        let index = tries.len() - 1;
+        self.state().proto.code[instruction] = Instruction::Try(index);
+    }
+
+    fn compile_export_declaration(&mut self, declaration: &Node) {
+        let name = match declaration {
+            Node::FunctionDeclaration(name, ..)
+            | Node::ClassDeclaration(name, ..)
+            | Node::VariableDeclaration(name, ..) => name,
+            _ => {
+                return self.raise(InterpreterError::UnexpectedNode(Box::new(
+                    declaration.clone(),
+                )))
+            }
+        };
+        // only top level declarations can be exported
+        if self.functions.len() == 1 && self.state().scopes.is_empty() {
+            let name = self.name(name);
+            self.emit(Instruction::Export(name));
+        } else {
+            let error = self.error(InterpreterError::UnexpectedNode(Box::new(
+                declaration.clone(),
+            )));
+            self.emit(Instruction::CheckExport(error));
+        }
+        self.compile_statement(declaration);
+    }
+
+    /// Expressions push exactly one value
+    fn compile_expression(&mut self, node: &Node) {
+        match node {
+            Node::IntegerLiteral(value) => {
+                let index = self.constant(Value::from(*value as isize));
+                self.emit(Instruction::Constant(index));
+            }
+            Node::DecimalLiteral(value) => {
+                let index = self.constant(Value::from(*value));
+                self.emit(Instruction::Constant(index));
+            }
+            Node::StringLiteral(value) => {
+                let index = self.constant(Value::from(value.as_str()));
+                self.emit(Instruction::Constant(index));
+            }
+            Node::ArrayExpression(items) => {
+                for item in items {
+                    self.compile_expression(item);
+                }
+                self.emit(Instruction::Array(items.len()));
+            }
+            Node::ObjectExpression(properties) => {
+                let mut keys = vec![];
+                for property in properties {
+                    match &**property {
+                        Node::Property(key, value) => {
+                            self.compile_expression(value);
+                            keys.push(key.clone());
+                        }
+                        node => {
+                            return self
+                                .raise(InterpreterError::UnexpectedNode(Box::new(node.clone())))
+                        }
+                    }
+                }
+                let shapes = &mut self.state().proto.shapes;
+                shapes.push(keys);
+                let index = shapes.len() - 1;
+                self.emit(Instruction::Object(index));
+            }
+            Node::Identifier(name) => {
+                let (variable, _) = self.resolve_variable(name);
+                self.emit(Instruction::Get(variable));
+            }
+            Node::BinaryExpression(left, operator, right) => {
+                self.compile_expression(left);
+                self.compile_expression(right);
+                self.emit(Instruction::Binary(operator.clone()));
+            }
+            Node::LogicalExpression(left, operator, right) => {
+                self.compile_expression(left);
+                let end = self.emit(Instruction::Logical(operator.clone(), 0));
+                self.compile_expression(right);
+                self.emit(Instruction::CheckBoolean);
+                self.patch(end);
+            }
+            Node::UnaryExpression(expression, operator) => {
+                self.compile_expression(expression);
+                let error = || InterpreterError::UnexpectedNode(expression.clone());
+                match operator {
+                    UnaryOperator::Plus => {}
+                    UnaryOperator::Minus => {
+                        let error = self.error(error());
+                        self.emit(Instruction::Negate(error));
+                    }
+                    UnaryOperator::Negation => {
+                        let error = self.error(error());
+                        self.emit(Instruction::Not(error));
+                    }
+                    operator => {
+                        self.raise(InterpreterError::UnsupportedUnaryOperator(operator.clone()))
+                    }
+                }
+            }
+            Node::AssignmentExpression(left, operator, right) => {
+                self.compile_assignment(left, operator, right)
+            }
+            Node::MemberExpression(object, property, computed) => {
+                self.compile_expression(object);
+                if *computed {
+                    self.compile_expression(property);
+                    self.emit(Instruction::GetIndex);
+                    return;
+                }
+                match &**property {
+                    Node::Identifier(name) => {
+                        let index = self.constant(Value::from(name.as_str()));
+                        self.emit(Instruction::GetProperty(index));
+                    }
+                    _ => self.raise(InterpreterError::UnexpectedNode(property.clone())),
+                }
+            }
+            Node::CallExpression(callee, arguments) => self.compile_call(callee, arguments),
+            Node::FunctionExpression(parameters, body) => {
+                self.compile_closure("anonymous", parameters, body);
+            }
+            Node::VariableDeclaration(..)
+            | Node::FunctionDeclaration(..)
+            | Node::ClassDeclaration(..)
+            | Node::BlockStatement(_)
+            | Node::IfStatement(..)
+            | Node::WhileStatement(..)
+            | Node::ForInStatement(..)
+            | Node::TryStatement(..)
+            | Node::ImportDeclaration(..)
+            | Node::ExportDeclaration(_)
+            | Node::ThrowStatement(_)
+            | Node::ReturnStatement(_)
+            | Node::BreakStatement
+            | Node::ContinueStatement => {
+                self.compile_statement(node);
+                self.emit(Instruction::Null);
+            }
+            node => self.raise(InterpreterError::UnsupportedNode(Box::new(node.clone()))),
+        }
+    }
+
+    fn compile_call(&mut self, callee: &Node, arguments: &[Box<Node>]) {
+        for argument in arguments {
+            self.compile_expression(argument);
+        }
+        match callee {
+            Node::Identifier(name) if name == "super" => {
+                let name = self.name("__new__");
+                self.emit(Instruction::SuperCall(name, arguments.len()));
+            }
+            Node::MemberExpression(object, property, false) => match (&**object, &**property) {
+                (Node::Identifier(object_name), Node::Identifier(method_name))
+                    if object_name == "super" =>
+                {
+                    let name = self.name(method_name);
+                    self.emit(Instruction::SuperCall(name, arguments.len()));
+                }
+                (object, Node::Identifier(method_name)) => {
+                    self.compile_expression(object);
+                    let name = self.name(method_name);
+                    self.emit(Instruction::CallMethod(name, arguments.len()));
+                }
+                _ => {
+                    self.compile_expression(callee);
+                    self.emit(Instruction::Call(arguments.len()));
+                }
+            },
+            callee => {
+                self.compile_expression(callee);
+                self.emit(Instruction::Call(arguments.len()));
+            }
+        }
+    }
+
+    fn compile_assignment(&mut self, left: &Node, operator: &AssignmentOperator, right: &Node) {
+        match left {
+            Node::Identifier(name) => {
+                let (variable, is_constant) = self.resolve_variable(name);
+                if *operator == AssignmentOperator::Equals {
+                    self.compile_expression(right);
+                } else {
+                    self.emit(Instruction::Get(variable));
+                    self.compile_expression(right);
+                    self.emit(Instruction::Binary(get_binary_operator(operator.clone())));
+                }
+                if is_constant {
+                    return self.raise(InterpreterError::ReassignConstant(name.clone()));
+                }
+                self.emit(Instruction::Set(variable));
+            }
+            Node::MemberExpression(object, property, computed) => {
+                self.compile_expression(object);
+                if *computed {
+                    self.compile_expression(property);
+                } else {
+                    match &**property {
+                        Node::Identifier(name) => {
+                            let index = self.constant(Value::from(name.as_str()));
+                            self.emit(Instruction::Constant(index));
+                        }
+                        _ => {
+                            return self.raise(InterpreterError::UnsupportedNode(property.clone()))
+                        }
+                    }
+                }
+                if *operator != AssignmentOperator::Equals {
+                    self.emit(Instruction::GetForUpdate {
+                        computed: *computed,
+                    });
+                    self.compile_expression(right);
+                    self.emit(Instruction::Binary(get_binary_operator(operator.clone())));
+                } else {
+                    self.compile_expression(right);
+                }
+                self.emit(Instruction::SetMember {
+                    computed: *computed,
+                });
+            }
+            left => self.raise(InterpreterError::InvalidAssignFactor(Box::new(
+                left.clone(),
+            ))),
+        }
+    }
+
+    /// Pushes the closure, or fails like the tree walker when the parameters are invalid
+    fn compile_closure(&mut self, name: &str, parameters: &[Box<Node>], body: &Node) -> bool {
+        match self.compile_function(name, parameters, body) {
+            Ok(function) => {
+                self.emit(Instruction::Closure(function));
+                true
+            }
+            Err(error) => {
+                self.raise(error);
+                false
+            }
+        }
+    }
+
+    fn compile_function(
+        &mut self,
+        name: &str,
+        parameters: &[Box<Node>],
+        body: &Node,
+    ) -> Result<usize, InterpreterError> {
+        let parameters = Self::compile_parameters(parameters)?;
+
+        let mut state = FunctionState::new(name.to_string());
+        state.scopes.push(vec![]);
+        self.functions.push(state);
+        for (parameter, _) in &parameters {
+            // parameters are constants, like in the tree walker
+            self.add_local(&parameter.name, true, true);
+        }
+        // defaults are evaluated on every call, in order, so they can refer to previous parameters
+        for (slot, (_, default_value)) in parameters.iter().enumerate() {
+            if let Some(default_value) = default_value {
+                let skip = self.emit(Instruction::JumpIfDeclared(slot, 0));
+                self.compile_expression(default_value);
+                self.emit(Instruction::Declare(Variable::Local(slot), true));
+                self.patch(skip);
+            }
+        }
+        self.compile_statement(body);
+
+        let mut state = self.functions.pop().unwrap();
+        state.proto.parameters = parameters
+            .into_iter()
+            .map(|(parameter, _)| parameter)
+            .collect();
+        let functions = &mut self.state().proto.functions;
+        functions.push(Arc::new(state.proto));
+        Ok(functions.len() - 1)
+    }
+
+    /// Same checks as `Interpreter::eval_parameters`
+    fn compile_parameters(
+        parameters: &[Box<Node>],
+    ) -> Result<Vec<(Parameter, Option<&Node>)>, InterpreterError> {
+        let mut compiled: Vec<(Parameter, Option<&Node>)> = vec![];
+        for parameter in parameters {
+            let invalid = || InterpreterError::InvalidFunctionParameter(parameter.clone());
+            if compiled
+                .last()
+                .is_some_and(|(previous, _)| previous.is_rest)
+            {
+                return Err(invalid());
+            }
+            let (name, default_value, is_rest) = match &**parameter {
+                Node::Identifier(name) => {
+                    if let Some((previous, _)) = compiled.last() {
+                        if previous.has_default {
+                            return Err(InterpreterError::InvalidDefaultParameter(
+                                previous.name.clone(),
+                            ));
+                        }
+                    }
+                    (name, None, false)
+                }
+                Node::AssignmentExpression(left, operator, right) => {
+                    if *operator != AssignmentOperator::Equals {
+                        return Err(invalid());
+                    }
+                    match &**left {
+                        Node::Identifier(name) => (name, Some(&**right), false),
+                        _ => return Err(InterpreterError::InvalidAssignFactor(left.clone())),
+                    }
+                }
+                Node::RestElement(name) => (name, None, true),
+                _ => return Err(invalid()),
+            };
+            compiled.push((
+                Parameter {
+                    name: name.clone(),
+                    has_default: default_value.is_some(),
+                    is_rest,
+                },
+                default_value,
+            ));
+        }
+        Ok(compiled)
+    }
+
+    fn compile_class(&mut self, name: &str, super_class: Option<&Node>, body: &[Box<Node>]) {
+        if let Some(super_class) = super_class {
+            self.compile_expression(super_class);
+        }
+        let mut members = vec![];
+        for member in body {
+            match &**member {
+                Node::PropertyDefinition(name, value, is_static) => {
+                    self.compile_expression(value);
+                    members.push(ClassMember::Property {
+                        name: name.clone(),
+                        is_static: *is_static,
+                    });
+                }
+                Node::MethodDefinition(name, parameters, body, is_static) => {
+                    match self.compile_function(name, parameters, body) {
+                        Ok(function) => members.push(ClassMember::Method {
+                            name: name.clone(),
+                            function,
+                            is_static: *is_static,
+                        }),
+                        Err(error) => return self.raise(error),
+                    }
+                }
+                _ => {}
+            }
+        }
+        let classes = &mut self.state().proto.classes;
+        classes.push(ClassProto {
+            name: name.to_string(),
+            has_super_class: super_class.is_some(),
+            members,
+        });
+        let index = classes.len() - 1;
+        self.emit(Instruction::Class(index));
+    }
+}
@@ -0,0 +1,1049 @@
+use std::{
+    collections::HashMap,
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use pl_ast::{LogicalOperator, Node};
+
+use crate::{
+    check_argument_count, collect_cells, get_element, get_items, macros::bail, parse_source,
+    resolve_index, setup_default_environment, ArrayValue, Cell, ClassInstanceValue, ClassProperty,
+    ClassValue, ClosureValue, CompiledMethod, Engine, EnvironmentHandle, EnvironmentId,
+    InterpreterError, Key, Module, Modules, ObjectValue, Prototypes, Resolver, ScopeState, Value,
+    MIN_COLLECTION_THRESHOLD,
+};
+
+use super::{
+    Capture, ClassMember, ClassProto, Compiler, FunctionProto, ImportProto, Instruction, Variable,
+};
+
+#[derive(Clone)]
+enum Slot {
+    /// Not declared yet
+    Empty,
+    Value(Value),
+    /// Captured by a closure, see `Vm::capture_local`
+    Cell(Cell),
+}
+
+struct Frame {
+    closure: Arc<ClosureValue>,
+    slots: Vec<Slot>,
+    stack: Vec<Value>,
+    /// Items of the running for-in loops
+    iterators: Vec<std::vec::IntoIter<Value>>,
+    receiver: Option<Value>,
+    /// Shared with the closures created in the frame, once there are any
+    receiver_cell: Option<Cell>,
+    /// Class of the running method, `super` is resolved against its super class
+    class: Option<Arc<ClassValue>>,
+}
+
+impl Frame {
+    fn new(closure: Arc<ClosureValue>, receiver: Option<Value>) -> Self {
+        // methods get the object they are called on, other functions keep `self` of their creator
+        let (receiver, receiver_cell, class) = match receiver {
+            Some(receiver) => (
+                Some(receiver),
+                None,
+                closure
+                    .class
+                    .clone()
+                    .or_else(|| closure.enclosing_class.clone()),
+            ),
+            None => (
+                closure.receiver.as_ref().and_then(|cell| {
+                    cell.lock()
+                        .expect("frame.new(): failed to get receiver")
+                        .clone()
+                }),
+                closure.receiver.clone(),
+                closure.enclosing_class.clone(),
+            ),
+        };
+        Self {
+            slots: vec![Slot::Empty; closure.function.locals.len()],
+            stack: vec![],
+            iterators: vec![],
+            receiver,
+            receiver_cell,
+            class,
+            closure,
+        }
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("frame.pop(): failed to get value")
+    }
+
+    fn pop_many(&mut self, count: usize) -> Vec<Value> {
+        let length = self.stack.len();
+        self.stack.split_off(length - count)
+    }
+
+    fn receiver(&self) -> Option<Value> {
+        match &self.receiver_cell {
+            Some(cell) => cell
+                .lock()
+                .expect("frame.receiver(): failed to get receiver")
+                .clone(),
+            None => self.receiver.clone(),
+        }
+    }
+
+    fn globals(&self) -> EnvironmentId {
+        self.closure.globals.id()
+    }
+}
+
+enum Flow {
+    /// Reached the end of the range
+    Done,
+    /// Jump out of the range, e.g. `break` inside of a try statement
+    Jump(usize),
+    Return(Value),
+}
+
+/// Runs scripts compiled to bytecode, with the same builtins and semantics as `Interpreter`
+pub struct Vm {
+    scope_state: ScopeState,
+    /// Environment with the builtins, the main script runs in it
+    global_env: EnvironmentHandle,
+    modules: Modules,
+    /// Every captured variable, see `collect_cells`
+    cells: Vec<Cell>,
+    /// Number of cells that triggers the next collection
+    collection_threshold: usize,
+    /// Methods of the builtin values, including the ones added by scripts
+    prototypes: Prototypes,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        let mut scope_state = ScopeState::new();
+        let global_env = setup_default_environment(&mut scope_state);
+        Self {
+            scope_state,
+            global_env,
+            modules: Modules::default(),
+            cells: vec![],
+            collection_threshold: MIN_COLLECTION_THRESHOLD,
+            prototypes: Prototypes::new(),
+        }
+    }
+
+    pub fn with_search_paths(mut self, search_paths: Vec<PathBuf>) -> Self {
+        self.modules.search_paths = search_paths;
+        self
+    }
+
+    pub fn global_env(&self) -> EnvironmentId {
+        self.global_env.id()
+    }
+
+    pub fn scope_state(&self) -> &ScopeState {
+        &self.scope_state
+    }
+
+    pub fn run_from_source(&mut self, source: String) -> Result<(), String> {
+        let ast = parse_source(source)?;
+        self.run(&ast, self.global_env.clone())
+            .map_err(|err| format!("{}", err))
+    }
+
+    /// Runs the file as the main module, so its imports are resolved relative to it
+    pub fn run_file(&mut self, path: &Path) -> Result<(), String> {
+        let source = fs::read_to_string(path).map_err(|err| format!("{}", err))?;
+        let ast = parse_source(source)?;
+        let path = path.canonicalize().map_err(|err| format!("{}", err))?;
+        self.modules
+            .stack
+            .push(Module::new(path, self.global_env.clone()));
+        let result = self.run(&ast, self.global_env.clone());
+        self.modules.stack.pop();
+        result.map_err(|err| format!("{}", err))
+    }
+
+    pub fn run(
+        &mut self,
+        program: &Node,
+        globals: EnvironmentHandle,
+    ) -> Result<(), InterpreterError> {
+        // unresolved names are reported before anything runs, like `Interpreter` does
+        Resolver::new(&self.scope_state, globals.id()).check_program(program)?;
+        let function = Arc::new(Compiler::compile_program(program));
+        let length = function.code.len();
+        let closure = ClosureValue {
+            function,
+            upvalues: Arc::from([]),
+            globals,
+            receiver: None,
+            enclosing_class: None,
+            class: None,
+        };
+        let mut frame = Frame::new(Arc::new(closure), None);
+        match self.execute(&mut frame, 0..length)? {
+            Flow::Return(value) => bail!(InterpreterError::Return(value)),
+            _ => Ok(()),
+        }
+    }
+
+    fn execute(
+        &mut self,
+        frame: &mut Frame,
+        range: Range<usize>,
+    ) -> Result<Flow, InterpreterError> {
+        let function = frame.closure.function.clone();
+        let mut pc = range.start;
+
+        macro_rules! jump {
+            ($target:expr) => {{
+                let target = $target;
+                if target == range.end {
+                    return Ok(Flow::Done);
+                }
+                if !range.contains(&target) {
+                    return Ok(Flow::Jump(target));
+                }
+                pc = target;
+            }};
+        }
+
+        while pc < range.end {
+            let instruction = &function.code[pc];
+            pc += 1;
+            match instruction {
+                Instruction::Constant(index) => {
+                    frame.stack.push(function.constants[*index].clone())
+                }
+                Instruction::Null => frame.stack.push(Value::Null),
+                Instruction::Pop => {
+                    frame.pop();
+                }
+                Instruction::Get(variable) => {
+                    let value = self.get_variable(frame, *variable, &function)?;
+                    frame.stack.push(value);
+                }
+                Instruction::Set(variable) => {
+                    let value = frame.pop();
+                    let value = self.set_variable(frame, *variable, value, &function, false)?;
+                    frame.stack.push(value);
+                }
+                Instruction::Declare(variable, is_constant) => {
+                    let value = frame.pop();
+                    self.declare_variable(frame, *variable, value, *is_constant, &function)?;
+                }
+                Instruction::ClearLocal(slot) => frame.slots[*slot] = Slot::Empty,
+                Instruction::JumpIfDeclared(slot, target) => {
+                    let is_declared = match &frame.slots[*slot] {
+                        Slot::Empty => false,
+                        Slot::Value(_) => true,
+                        Slot::Cell(cell) => {
+                            cell.lock().expect("execute: failed to get cell").is_some()
+                        }
+                    };
+                    if is_declared {
+                        jump!(*target);
+                    }
+                }
+                Instruction::Array(length) => {
+                    let items = frame.pop_many(*length);
+                    frame.stack.push(Value::from(ArrayValue::from(items)));
+                }
+                Instruction::Object(shape) => {
+                    let keys = &function.shapes[*shape];
+                    let values = frame.pop_many(keys.len());
+                    let map = keys
+                        .iter()
+                        .cloned()
+                        .zip(values)
+                        .collect::<HashMap<Key, Value>>();
+                    frame.stack.push(Value::from(ObjectValue::from(map)));
+                }
+                Instruction::GetProperty(index) => {
+                    let object = frame.pop();
+                    let key = match &function.constants[*index] {
+                        Value::String(key) => key,
+                        key => bail!(InterpreterError::UnsupportedValue(key.clone())),
+                    };
+                    let value = self.get_property(&object, key)?;
+                    frame.stack.push(value);
+                }
+                Instruction::GetIndex => {
+                    let key = frame.pop();
+                    let object = frame.pop();
+                    let value = match key {
+                        Value::Integer(index) => get_element(object, index)?,
+                        Value::String(key) => self.get_property(&object, &key)?,
+                        key => bail!(InterpreterError::UnsupportedValue(key)),
+                    };
+                    frame.stack.push(value);
+                }
+                Instruction::GetForUpdate { computed } => {
+                    let length = frame.stack.len();
+                    let object = &frame.stack[length - 2];
+                    let key = &frame.stack[length - 1];
+                    let value = self.get_for_update(object, key, *computed)?;
+                    frame.stack.push(value);
+                }
+                Instruction::SetMember { computed } => {
+                    let value = frame.pop();
+                    let key = frame.pop();
+                    let object = frame.pop();
+                    let value = self.set_member(object, key, value, *computed)?;
+                    frame.stack.push(value);
+                }
+                Instruction::Binary(operator) => {
+                    let right = frame.pop();
+                    let left = frame.pop();
+                    let value = self.eval_binary_values(left, right, operator.clone())?;
+                    frame.stack.push(value);
+                }
+                Instruction::Negate(error) => {
+                    let value = frame.pop();
+                    let value = self.negate(value, || function.errors[*error].clone())?;
+                    frame.stack.push(value);
+                }
+                Instruction::Not(error) => match frame.pop() {
+                    Value::Boolean(value) => frame.stack.push(Value::from(!value)),
+                    _ => bail!(function.errors[*error].clone()),
+                },
+                Instruction::Jump(target) => jump!(*target),
+                Instruction::JumpIfFalse(target) => match frame.pop() {
+                    Value::Boolean(true) => {}
+                    Value::Boolean(false) => jump!(*target),
+                    condition => bail!(InterpreterError::InvalidCondition(condition)),
+                },
+                Instruction::Logical(operator, target) => {
+                    let left = match frame.stack.last() {
+                        Some(Value::Boolean(left)) => *left,
+                        _ => bail!(InterpreterError::InvalidValue(
+                            frame.pop(),
+                            "boolean".to_string()
+                        )),
+                    };
+                    // the left side stays on the stack as the result if it decides it
+                    let is_decided = match operator {
+                        LogicalOperator::And => !left,
+                        LogicalOperator::Or => left,
+                    };
+                    if is_decided {
+                        jump!(*target);
+                    } else {
+                        frame.pop();
+                    }
+                }
+                Instruction::CheckBoolean => {
+                    if !matches!(frame.stack.last(), Some(Value::Boolean(_))) {
+                        bail!(InterpreterError::InvalidValue(
+                            frame.pop(),
+                            "boolean".to_string()
+                        ))
+                    }
+                }
+                Instruction::IterStart => {
+                    let items = get_items(frame.pop())?;
+                    frame.iterators.push(items.into_iter());
+                }
+                Instruction::IterNext(target) => {
+                    let item = frame
+                        .iterators
+                        .last_mut()
+                        .expect("execute: failed to get iterator")
+                        .next();
+                    match item {
+                        Some(item) => frame.stack.push(item),
+                        None => jump!(*target),
+                    }
+                }
+                Instruction::IterEnd => {
+                    frame.iterators.pop();
+                }
+                Instruction::Call(count) => {
+                    let callee = frame.pop();
+                    let args = frame.pop_many(*count);
+                    let value = self.call_value(callee, args)?;
+                    frame.stack.push(value);
+                }
+                Instruction::CallMethod(name, count) => {
+                    let object = frame.pop();
+                    let args = frame.pop_many(*count);
+                    let value = self.call_method(object, &function.names[*name], args)?;
+                    frame.stack.push(value);
+                }
+                Instruction::SuperCall(name, count) => {
+                    let args = frame.pop_many(*count);
+                    let (class, object) = match (&frame.class, frame.receiver()) {
+                        (Some(class), Some(object)) => (class.clone(), object),
+                        _ => bail!(InterpreterError::InvalidSuper),
+                    };
+                    let value = self.call_super(&class, object, &function.names[*name], args)?;
+                    frame.stack.push(value);
+                }
+                Instruction::Closure(index) => {
+                    let closure = self.make_closure(frame, &function.functions[*index]);
+                    frame.stack.push(Value::from(closure));
+                }
+                Instruction::Class(index) => {
+                    let class = self.make_class(frame, &function.classes[*index], &function);
+                    frame.stack.push(Value::from(class));
+                }
+                Instruction::Extend => {
+                    if let Value::Class(class) = frame.pop() {
+                        self.prototypes.extend(class);
+                    }
+                }
+                Instruction::Return => return Ok(Flow::Return(frame.pop())),
+                Instruction::Throw => bail!(InterpreterError::Throw(frame.pop())),
+                Instruction::Try(index) => {
+                    let block = &function.tries[*index];
+                    let stack_length = frame.stack.len();
+                    let iterators_length = frame.iterators.len();
+                    let mut result = self.execute(frame, block.body.clone());
+
+                    if let Some(handler) = &block.handler {
+                        if let Err(err) = result {
+                            result = if err.is_catchable() {
+                                frame.stack.truncate(stack_length);
+                                frame.iterators.truncate(iterators_length);
+                                frame.stack.push(err.into_value());
+                                self.execute(frame, handler.clone())
+                            } else {
+                                Err(err)
+                            };
+                        }
+                    }
+
+                    // finally runs for errors and control flow as well,
+                    // an error or control flow inside of it replaces the original result
+                    if let Some(finalizer) = &block.finalizer {
+                        frame.stack.truncate(stack_length);
+                        frame.iterators.truncate(iterators_length);
+                        match self.execute(frame, finalizer.clone())? {
+                            Flow::Done => {}
+                            flow => result = Ok(flow),
+                        }
+                    }
+
+                    match result? {
+                        Flow::Done => jump!(block.end),
+                        Flow::Jump(target) => jump!(target),
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                    }
+                }
+                Instruction::Import(index) => self.import(frame, &function.imports[*index])?,
+                Instruction::Export(name) => {
+                    if let Some(module) = self.modules.stack.last_mut() {
+                        module.exports.push(function.names[*name].clone());
+                    }
+                }
+                Instruction::CheckExport(error) => {
+                    if !self.modules.stack.is_empty() {
+                        bail!(function.errors[*error].clone())
+                    }
+                }
+                Instruction::Raise(error) => bail!(function.errors[*error].clone()),
+            }
+        }
+
+        Ok(Flow::Done)
+    }
+
+    fn get_variable(
+        &self,
+        frame: &Frame,
+        variable: Variable,
+        function: &FunctionProto,
+    ) -> Result<Value, InterpreterError> {
+        let cell = match variable {
+            Variable::Local(slot) => match &frame.slots[slot] {
+                Slot::Value(value) => return Ok(value.clone()),
+                Slot::Cell(cell) => (cell, &function.locals[slot]),
+                Slot::Empty => bail!(InterpreterError::UnresolvedVariable(
+                    function.locals[slot].clone()
+                )),
+            },
+            Variable::Upvalue(index) => return self.get_upvalue(frame, index, function),
+            Variable::Global(name) => {
+                return self.lookup_global(&function.names[name], frame.globals())
+            }
+            Variable::Receiver(name) => match frame.receiver() {
+                Some(receiver) => return Ok(receiver),
+                None => return self.lookup_global(&function.names[name], frame.globals()),
+            },
+        };
+        let (cell, name) = cell;
+        let value = cell
+            .lock()
+            .expect("vm.get_variable(): failed to get cell")
+            .clone();
+        match value {
+            Some(value) => Ok(value),
+            None => self.lookup_global(name, frame.globals()),
+        }
+    }
+
+    /// Captured variable, a closure can run before the declaration it captured,
+    /// the tree walker finds the outer variable then, see `Upvalue::fallback`
+    fn get_upvalue(
+        &self,
+        frame: &Frame,
+        mut index: usize,
+        function: &FunctionProto,
+    ) -> Result<Value, InterpreterError> {
+        loop {
+            let value = frame.closure.upvalues[index]
+                .lock()
+                .expect("vm.get_upvalue(): failed to get cell")
+                .clone();
+            if let Some(value) = value {
+                return Ok(value);
+            }
+            let upvalue = &function.upvalues[index];
+            match upvalue.fallback {
+                Some(fallback) => index = fallback,
+                None => return self.lookup_global(&upvalue.name, frame.globals()),
+            }
+        }
+    }
+
+    fn set_variable(
+        &mut self,
+        frame: &mut Frame,
+        variable: Variable,
+        value: Value,
+        function: &FunctionProto,
+        ignore_constant: bool,
+    ) -> Result<Value, InterpreterError> {
+        let (cell, name) = match variable {
+            Variable::Local(slot) => match &mut frame.slots[slot] {
+                Slot::Value(current) => {
+                    *current = value.clone();
+                    return Ok(value);
+                }
+                Slot::Cell(cell) => (cell.clone(), &function.locals[slot]),
+                Slot::Empty => bail!(InterpreterError::UnresolvedVariable(
+                    function.locals[slot].clone()
+                )),
+            },
+            Variable::Upvalue(index) => {
+                return self.set_upvalue(frame, index, value, function, ignore_constant)
+            }
+            Variable::Global(name) => {
+                return self.scope_state.assign_variable(
+                    &function.names[name],
+                    value,
+                    frame.globals(),
+                    ignore_constant,
+                )
+            }
+            // the caller still points to the receiver, so its contents are replaced instead
+            Variable::Receiver(name) => match frame.receiver() {
+                Some(receiver) => {
+                    if !receiver.replace_contents(&value) {
+                        bail!(InterpreterError::InvalidSelfAssignment(receiver, value))
+                    }
+                    return Ok(receiver);
+                }
+                None => {
+                    return self.scope_state.assign_variable(
+                        &function.names[name],
+                        value,
+                        frame.globals(),
+                        ignore_constant,
+                    )
+                }
+            },
+        };
+        let mut current = cell.lock().expect("vm.set_variable(): failed to get cell");
+        match &mut *current {
+            Some(current) => *current = value.clone(),
+            None => {
+                drop(current);
+                return self.scope_state.assign_variable(
+                    name,
+                    value,
+                    frame.globals(),
+                    ignore_constant,
+                );
+            }
+        }
+        Ok(value)
+    }
+
+    /// See `Vm::get_upvalue`
+    fn set_upvalue(
+        &mut self,
+        frame: &Frame,
+        mut index: usize,
+        value: Value,
+        function: &FunctionProto,
+        ignore_constant: bool,
+    ) -> Result<Value, InterpreterError> {
+        loop {
+            let mut current = frame.closure.upvalues[index]
+                .lock()
+                .expect("vm.set_upvalue(): failed to get cell");
+            if let Some(current) = &mut *current {
+                *current = value.clone();
+                return Ok(value);
+            }
+            drop(current);
+            let upvalue = &function.upvalues[index];
+            match upvalue.fallback {
+                Some(fallback) => index = fallback,
+                None => {
+                    return self.scope_state.assign_variable(
+                        &upvalue.name,
+                        value,
+                        frame.globals(),
+                        ignore_constant,
+                    )
+                }
+            }
+        }
+    }
+
+    fn declare_variable(
+        &mut self,
+        frame: &mut Frame,
+        variable: Variable,
+        value: Value,
+        is_constant: bool,
+        function: &FunctionProto,
+    ) -> Result<(), InterpreterError> {
+        match variable {
+            Variable::Local(slot) => {
+                let exists =
+                    || InterpreterError::VariableDeclarationExist(function.locals[slot].clone());
+                match &frame.slots[slot] {
+                    Slot::Empty => frame.slots[slot] = Slot::Value(value),
+                    Slot::Cell(cell) => {
+                        let mut current = cell
+                            .lock()
+                            .expect("vm.declare_variable(): failed to get cell");
+                        if current.is_some() {
+                            bail!(exists())
+                        }
+                        *current = Some(value);
+                    }
+                    Slot::Value(_) => bail!(exists()),
+                }
+            }
+            Variable::Global(name) => {
+                let env = frame.globals();
+                let scope = match self.scope_state.get_scope_mut(env) {
+                    Some(scope) => scope,
+                    None => bail!(InterpreterError::UnresolvedEnvironment(env)),
+                };
+                scope.declare_variable(function.names[name].clone(), value, is_constant)?;
+            }
+            variable => panic!("vm.declare_variable(): cannot declare {:?}", variable),
+        }
+        Ok(())
+    }
+
+    fn lookup_global(&self, name: &str, env: EnvironmentId) -> Result<Value, InterpreterError> {
+        match self.scope_state.get_scope(env) {
+            Some(scope) => scope.lookup_variable(name, &self.scope_state),
+            None => bail!(InterpreterError::UnresolvedEnvironment(env)),
+        }
+    }
+
+    /// Moves the local into a cell that the frame shares with the closure
+    fn capture_local(&mut self, frame: &mut Frame, slot: usize) -> Cell {
+        let value = match std::mem::replace(&mut frame.slots[slot], Slot::Empty) {
+            Slot::Cell(cell) => {
+                frame.slots[slot] = Slot::Cell(cell.clone());
+                return cell;
+            }
+            Slot::Value(value) => Some(value),
+            // declared later, e.g. a local function that calls the one declared after it
+            Slot::Empty => None,
+        };
+        let cell = self.new_cell(value);
+        frame.slots[slot] = Slot::Cell(cell.clone());
+        cell
+    }
+
+    fn new_cell(&mut self, value: Option<Value>) -> Cell {
+        if self.cells.len() >= self.collection_threshold {
+            collect_cells(&mut self.cells);
+            // collect again once the number of live cells doubles
+            self.collection_threshold = (self.cells.len() * 2).max(MIN_COLLECTION_THRESHOLD);
+        }
+        let cell = Arc::new(Mutex::new(value));
+        self.cells.push(cell.clone());
+        cell
+    }
+
+    fn make_closure(&mut self, frame: &mut Frame, function: &Arc<FunctionProto>) -> ClosureValue {
+        let mut upvalues = Vec::with_capacity(function.upvalues.len());
+        for upvalue in &function.upvalues {
+            let cell = match upvalue.capture {
+                Capture::Local(slot) => self.capture_local(frame, slot),
+                Capture::Upvalue(index) => frame.closure.upvalues[index].clone(),
+            };
+            upvalues.push(cell);
+        }
+        let receiver = match (&frame.receiver_cell, &frame.receiver) {
+            (Some(cell), _) => Some(cell.clone()),
+            (None, Some(receiver)) => {
+                let cell = self.new_cell(Some(receiver.clone()));
+                frame.receiver_cell = Some(cell.clone());
+                Some(cell)
+            }
+            (None, None) => None,
+        };
+        ClosureValue {
+            function: function.clone(),
+            upvalues: upvalues.into(),
+            globals: frame.closure.globals.clone(),
+            receiver,
+            enclosing_class: frame.class.clone(),
+            class: None,
+        }
+    }
+
+    fn make_class(
+        &mut self,
+        frame: &mut Frame,
+        proto: &ClassProto,
+        function: &FunctionProto,
+    ) -> ClassValue {
+        let property_count = proto
+            .members
+            .iter()
+            .filter(|member| matches!(member, ClassMember::Property { .. }))
+            .count();
+        let mut values = frame.pop_many(property_count).into_iter();
+        let super_class = match proto.has_super_class {
+            true => Some(frame.pop()),
+            false => None,
+        };
+
+        // methods are compiled closures, so the class doesn't need a declaration environment
+        let mut class = ClassValue {
+            name: proto.name.clone(),
+            ..Default::default()
+        };
+        if let Some(Value::Class(target_class)) = super_class {
+            // methods are looked up through the super class, see `ClassValue::get_compiled_method`
+            class.copy_properties(&target_class);
+            class.super_class = Some(target_class);
+        }
+        for member in &proto.members {
+            match member {
+                ClassMember::Property { name, is_static } => class.insert_property(ClassProperty {
+                    name: name.clone(),
+                    value: values
+                        .next()
+                        .expect("vm.make_class(): failed to get property"),
+                    is_static: *is_static,
+                }),
+                ClassMember::Method {
+                    name,
+                    function: index,
+                    is_static,
+                } => {
+                    let closure = self.make_closure(frame, &function.functions[*index]);
+                    class.compiled_methods.insert(
+                        name.clone(),
+                        CompiledMethod {
+                            closure: Arc::new(closure),
+                            is_static: *is_static,
+                        },
+                    );
+                }
+            }
+        }
+        class
+    }
+
+    fn get_property(&self, object: &Value, key: &str) -> Result<Value, InterpreterError> {
+        let fields = match object {
+            Value::Object(fields) => fields.clone(),
+            Value::ClassInstance(instance) => {
+                return Ok(self.get_instance_member(instance, key).unwrap_or_default())
+            }
+            Value::Class(class) => {
+                if let Some(class_prop) = class.get_static_property(key.to_string()) {
+                    return Ok(class_prop.value);
+                }
+                match class.get_static_compiled_method(key) {
+                    Some(closure) => return Ok(Value::from(closure)),
+                    None => bail!(InterpreterError::UnresolvedProperty(key.to_string())),
+                }
+            }
+            Value::String(_)
+            | Value::Integer(_)
+            | Value::Decimal(_)
+            | Value::Boolean(_)
+            | Value::Array(_) => {
+                // script extensions come first so they can override the builtin methods
+                let value = self
+                    .get_extension_method(object, key)
+                    .or_else(|| self.prototypes.get_property(object, key));
+                return Ok(value.unwrap_or_default());
+            }
+            _ => bail!(InterpreterError::UnexpectedValue(object.clone())),
+        };
+
+        let value = fields
+            .get_property(key)
+            .or_else(|| self.get_extension_method(object, key));
+        Ok(value.unwrap_or_default())
+    }
+
+    /// Own field of the instance, or the method of its class when there is no such field
+    fn get_instance_member(&self, instance: &ClassInstanceValue, key: &str) -> Option<Value> {
+        instance
+            .fields()
+            .get_property(key)
+            .or_else(|| instance.class().get_compiled_method(key).map(Value::from))
+    }
+
+    /// Method of a class that extends the type of the value, see `Prototypes::extend`
+    fn get_extension_method(&self, value: &Value, key: &str) -> Option<Value> {
+        self.prototypes
+            .find_extension(value, |class| class.get_compiled_method(key))
+            .map(Value::from)
+    }
+
+    /// Current value of `object[key]` for a compound assignment
+    fn get_for_update(
+        &self,
+        object: &Value,
+        key: &Value,
+        computed: bool,
+    ) -> Result<Value, InterpreterError> {
+        let value = match (object, key) {
+            (Value::Array(array), key) if computed => {
+                let index = match key {
+                    Value::Integer(index) => *index,
+                    index => bail!(InterpreterError::UnsupportedValue(index.clone())),
+                };
+                let index = resolve_index(index, array.len())?;
+                return Ok(array.get_element(index).unwrap_or_default());
+            }
+            (Value::Object(_) | Value::ClassInstance(_) | Value::Class(_), Value::String(key)) => {
+                let value = match object {
+                    Value::Object(object) => object.get_property(key),
+                    Value::ClassInstance(instance) => instance.fields().get_property(key),
+                    Value::Class(class) => class
+                        .get_static_property(key.to_string())
+                        .map(|property| property.value),
+                    _ => None,
+                };
+                (value, key)
+            }
+            (Value::Object(_) | Value::ClassInstance(_) | Value::Class(_), key) => {
+                bail!(InterpreterError::UnsupportedValue(key.clone()))
+            }
+            (object, _) => bail!(InterpreterError::InvalidValue(
+                object.clone(),
+                "object, class or array".to_string()
+            )),
+        };
+        match value {
+            (Some(value), _) => Ok(value),
+            (None, key) => bail!(InterpreterError::UnresolvedProperty(key.to_string())),
+        }
+    }
+
+    fn set_member(
+        &mut self,
+        object: Value,
+        key: Value,
+        value: Value,
+        computed: bool,
+    ) -> Result<Value, InterpreterError> {
+        match object {
+            Value::Array(array) if computed => {
+                let index = match key {
+                    Value::Integer(index) => index,
+                    index => bail!(InterpreterError::UnsupportedValue(index)),
+                };
+                let index = resolve_index(index, array.len())?;
+                array.set_element(index, value.clone());
+                Ok(value)
+            }
+            Value::Object(_) | Value::ClassInstance(_) | Value::Class(_) => {
+                let key = match key {
+                    Value::String(key) => key.to_string(),
+                    key => bail!(InterpreterError::UnsupportedValue(key)),
+                };
+                match object {
+                    // objects, instances and classes are shared by reference, so there is nothing to write back
+                    Value::Object(object) => object.assign_property(key, value.clone()),
+                    Value::ClassInstance(instance) => {
+                        instance.fields().assign_property(key, value.clone())
+                    }
+                    Value::Class(class) => class.assign_static_property(key, value.clone()),
+                    _ => {}
+                }
+                Ok(value)
+            }
+            object => bail!(InterpreterError::InvalidValue(
+                object,
+                "object, class or array".to_string()
+            )),
+        }
+    }
+
+    fn call_value(&mut self, callee: Value, args: Vec<Value>) -> Result<Value, InterpreterError> {
+        match callee {
+            Value::NativeFn(native_fn) => native_fn.callee().run(self, args),
+            Value::Closure(closure) => self.call_closure(closure, args, None),
+            Value::Class(class) => self.instantiate(class, args),
+            callee => bail!(InterpreterError::InvalidFunctionCallee(callee)),
+        }
+    }
+
+    /// Functions found on objects, instances and arrays are called with the object as `self`
+    fn call_method(
+        &mut self,
+        object: Value,
+        name: &str,
+        args: Vec<Value>,
+    ) -> Result<Value, InterpreterError> {
+        let callee = match &object {
+            Value::Object(fields) => fields
+                .get_property(name)
+                .or_else(|| self.get_extension_method(&object, name)),
+            Value::ClassInstance(instance) => self.get_instance_member(instance, name),
+            Value::String(_)
+            | Value::Integer(_)
+            | Value::Decimal(_)
+            | Value::Boolean(_)
+            | Value::Array(_) => {
+                // script extensions come first so they can override the builtin methods
+                let extension = self.get_extension_method(&object, name);
+                // builtin methods get the receiver as their first argument
+                if let (None, Some(Value::NativeFn(method))) =
+                    (&extension, self.prototypes.get_method(&object, name))
+                {
+                    let method = method.clone();
+                    return method.callee().run(self, [vec![object], args].concat());
+                }
+                extension
+            }
+            _ => {
+                let callee = self.get_property(&object, name)?;
+                return self.call_value(callee, args);
+            }
+        };
+        match callee {
+            Some(Value::Closure(closure)) => self.call_closure(closure, args, Some(object)),
+            Some(callee) => self.call_value(callee, args),
+            None => bail!(InterpreterError::UnresolvedProperty(name.to_string())),
+        }
+    }
+
+    fn call_closure(
+        &mut self,
+        closure: Arc<ClosureValue>,
+        mut args: Vec<Value>,
+        receiver: Option<Value>,
+    ) -> Result<Value, InterpreterError> {
+        let parameters = &closure.function.parameters;
+        let required_args = parameters
+            .iter()
+            .filter(|parameter| !parameter.has_default && !parameter.is_rest)
+            .count();
+        let rest = parameters.iter().position(|parameter| parameter.is_rest);
+        check_argument_count(required_args, parameters.len(), rest.is_some(), args.len())?;
+
+        let length = closure.function.code.len();
+        let mut frame = Frame::new(closure, receiver);
+        if let Some(rest) = rest {
+            let rest_args = args.split_off(rest.min(args.len()));
+            frame.slots[rest] = Slot::Value(Value::from(ArrayValue::from(rest_args)));
+        }
+        for (slot, arg) in args.into_iter().enumerate() {
+            frame.slots[slot] = Slot::Value(arg);
+        }
+
+        match self.execute(&mut frame, 0..length) {
+            Ok(Flow::Return(value)) => Ok(value),
+            Ok(_) => Ok(Value::Null),
+            // loop control must not leak into the loop the function was called from
+            Err(InterpreterError::Break) => {
+                bail!(InterpreterError::InvalidLoopControl("break".to_string()))
+            }
+            Err(InterpreterError::Continue) => {
+                bail!(InterpreterError::InvalidLoopControl("continue".to_string()))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn import(&mut self, frame: &mut Frame, import: &ImportProto) -> Result<(), InterpreterError> {
+        let env = frame.globals();
+        let module = self.load_module(&import.path, env)?;
+        let get_export = |name: &String| module.get_export(&self.scope_state, &import.path, name);
+
+        if !import.names.is_empty() {
+            // the compiler declares them, starting with the first name
+            for name in import.names.iter().rev() {
+                let value = get_export(name)?;
+                frame.stack.push(value);
+            }
+            return Ok(());
+        }
+
+        // `import "path"` brings in everything the module exports
+        let mut values = vec![];
+        for name in &module.exports {
+            values.push((name.clone(), get_export(name)?));
+        }
+        let scope = match self.scope_state.get_scope_mut(env) {
+            Some(scope) => scope,
+            None => bail!(InterpreterError::UnresolvedEnvironment(env)),
+        };
+        for (name, value) in values {
+            scope.declare_variable(name, value, true)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Engine for Vm {
+    fn scope_state_mut(&mut self) -> &mut ScopeState {
+        &mut self.scope_state
+    }
+
+    fn modules_mut(&mut self) -> &mut Modules {
+        &mut self.modules
+    }
+
+    fn call_class_method(
+        &mut self,
+        class: &Arc<ClassValue>,
+        receiver: Value,
+        name: &str,
+        args: Vec<Value>,
+    ) -> Result<Option<Value>, InterpreterError> {
+        match class.get_compiled_method(name) {
+            Some(method) => Ok(Some(self.call_closure(
+                Arc::new(method),
+                args,
+                Some(receiver),
+            )?)),
+            None => Ok(None),
+        }
+    }
+
+    fn run_module(&mut self, ast: Node, env: EnvironmentHandle) -> Result<(), InterpreterError> {
+        self.run(&ast, env)
+    }
+}
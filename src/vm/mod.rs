@@ -0,0 +1,7 @@
+mod chunk;
+mod compiler;
+mod machine;
+
+pub use chunk::*;
+pub use compiler::*;
+pub use machine::*;
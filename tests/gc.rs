@@ -0,0 +1,97 @@
+use pl_interpreter::{Interpreter, Value, MIN_COLLECTION_THRESHOLD};
+
+#[test]
+fn calls_release_their_environments() {
+    let mut interpreter = Interpreter::new();
+    let source = "
+fn add(a, b) {
+  let sum = a + b
+  return sum
+}
+
+let i = 0
+while i < 20000 {
+  add(i, 1)
+  i += 1
+}";
+    interpreter.run_from_source(source.to_string()).unwrap();
+
+    assert!(interpreter.scope_state().scopes.len() <= MIN_COLLECTION_THRESHOLD);
+}
+
+#[test]
+fn captured_environments_survive_collection() {
+    let mut interpreter = Interpreter::new();
+    let source = "
+fn counter() {
+  let count = 0
+  fn next() {
+    count += 1
+    return count
+  }
+  return next
+}
+
+let next = counter()
+let i = 0
+let last = 0
+while i < 5000 {
+  last = next()
+  i += 1
+}";
+    interpreter.run_from_source(source.to_string()).unwrap();
+
+    let scope = interpreter
+        .scope_state()
+        .get_scope(interpreter.global_env())
+        .unwrap();
+    assert!(matches!(
+        scope.variables.get("last"),
+        Some(Value::Integer(5000))
+    ));
+    assert!(interpreter.scope_state().scopes.len() <= MIN_COLLECTION_THRESHOLD);
+}
+
+#[test]
+fn finished_blocks_and_calls_are_released_right_away() {
+    let mut interpreter = Interpreter::new();
+    let scopes = interpreter.scope_state().scopes.len();
+    let source = "
+fn add(a, b) {
+  return a + b
+}
+
+let i = 0
+while i < 100 {
+  let next = add(i, 1)
+  i = next
+}";
+    interpreter.run_from_source(source.to_string()).unwrap();
+
+    // far below the collection threshold, so nothing was collected
+    assert_eq!(interpreter.scope_state().scopes.len(), scopes);
+}
+
+#[test]
+fn released_blocks_keep_captured_environments() {
+    let mut interpreter = Interpreter::new();
+    let source = "
+let readers = []
+let i = 0
+while i < 3 {
+  let value = i * 10
+  readers.push(fn() { return value })
+  i += 1
+}
+let last = readers[2]()";
+    interpreter.run_from_source(source.to_string()).unwrap();
+
+    let scope = interpreter
+        .scope_state()
+        .get_scope(interpreter.global_env())
+        .unwrap();
+    assert!(matches!(
+        scope.variables.get("last"),
+        Some(Value::Integer(20))
+    ));
+}
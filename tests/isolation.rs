@@ -0,0 +1,52 @@
+use std::thread;
+
+use pl_interpreter::{Interpreter, Value};
+
+/// Integer declared in the global environment of the interpreter
+fn global_integer(interpreter: &Interpreter, name: &str) -> Option<isize> {
+    let scope = interpreter
+        .scope_state()
+        .get_scope(interpreter.global_env())?;
+    match scope.variables.get(name)? {
+        Value::Integer(value) => Some(*value),
+        _ => None,
+    }
+}
+
+#[test]
+fn interpreters_do_not_share_globals() {
+    let mut first = Interpreter::new();
+    let mut second = Interpreter::new();
+
+    first.run_from_source("let shared = 1".to_string()).unwrap();
+    assert!(second.run_from_source("print(shared)".to_string()).is_err());
+
+    second
+        .run_from_source("let shared = 2".to_string())
+        .unwrap();
+    assert_eq!(global_integer(&first, "shared"), Some(1));
+    assert_eq!(global_integer(&second, "shared"), Some(2));
+}
+
+#[test]
+fn interpreters_run_on_parallel_threads() {
+    let handles = (1..=4)
+        .map(|step| {
+            thread::spawn(move || {
+                let mut interpreter = Interpreter::new();
+                let source = format!(
+                    "let total = 0\nlet i = 0\nwhile i < 1000 {{\n  total += {}\n  i += 1\n}}",
+                    step
+                );
+                interpreter.run_from_source(source).unwrap();
+                global_integer(&interpreter, "total")
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let totals = handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect::<Vec<Option<isize>>>();
+    assert_eq!(totals, vec![Some(1000), Some(2000), Some(3000), Some(4000)]);
+}
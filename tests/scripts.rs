@@ -0,0 +1,68 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Output of the script and the error it stopped with, if any,
+/// non-relative imports are looked up in test/modules/lib
+fn run_script(engine: &str, script: &Path) -> String {
+    let search_path = test_dir().join("modules").join("lib");
+    let output = Command::new(env!("CARGO_BIN_EXE_pl_interpreter_bin"))
+        .env("AMR_PATH", search_path)
+        .args(["--engine", engine])
+        .arg(script)
+        .output()
+        .expect("failed to run the interpreter");
+    let mut result = String::from_utf8_lossy(&output.stdout).into_owned();
+    result.push_str(&String::from_utf8_lossy(&output.stderr));
+    if !output.status.success() {
+        result.push_str(&format!("exit: {}\n", output.status.code().unwrap_or(-1)));
+    }
+    result
+}
+
+fn test_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("test")
+}
+
+/// Scripts directly in test/, the modules they import live in subdirectories
+fn scripts() -> Vec<PathBuf> {
+    let mut scripts = fs::read_dir(test_dir())
+        .expect("failed to read the test directory")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "amr"))
+        .collect::<Vec<PathBuf>>();
+    scripts.sort();
+    scripts
+}
+
+/// Every script in test/ has to print the same in both engines,
+/// and what test/<name>.out expects when the file exists
+#[test]
+fn engines_run_scripts_the_same() {
+    let mut failures = vec![];
+    for script in scripts() {
+        let tree = run_script("tree", &script);
+        let vm = run_script("vm", &script);
+        if tree != vm {
+            failures.push(format!(
+                "{}: engines differ\n--- tree\n{}--- vm\n{}",
+                script.display(),
+                tree,
+                vm
+            ));
+        }
+        if let Ok(expected) = fs::read_to_string(script.with_extension("out")) {
+            if tree != expected {
+                failures.push(format!(
+                    "{}: unexpected output\n--- expected\n{}--- tree\n{}",
+                    script.display(),
+                    expected,
+                    tree
+                ));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
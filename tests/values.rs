@@ -0,0 +1,35 @@
+use std::mem::size_of;
+
+use pl_interpreter::{ArrayValue, ObjectValue, Value};
+
+#[test]
+fn values_fit_in_three_words() {
+    assert!(size_of::<Value>() <= 3 * size_of::<usize>());
+}
+
+#[test]
+fn clones_share_arrays_and_objects() {
+    let array = ArrayValue::from(vec![Value::from(1_isize)]);
+    if let Value::Array(clone) = Value::from(array.clone()).clone() {
+        clone.append_element(Value::from(2_isize));
+    }
+    assert_eq!(array.len(), 2);
+
+    let object = ObjectValue::default();
+    if let Value::Object(clone) = Value::from(object.clone()).clone() {
+        clone.assign_property("key".into(), Value::from(true));
+    }
+    assert!(matches!(
+        object.get_property("key"),
+        Some(Value::Boolean(true))
+    ));
+}
+
+#[test]
+fn deep_copies_do_not_share() {
+    let array = ArrayValue::from(vec![Value::from(1_isize)]);
+    if let Value::Array(copy) = Value::from(array.clone()).deep_copy() {
+        copy.append_element(Value::from(2_isize));
+    }
+    assert_eq!(array.len(), 1);
+}